@@ -22,34 +22,28 @@ pub enum AnimationState {
 
 impl Animation {
     pub fn current_frame(&self, start_time: usize, now: usize) -> Rect {
-        if self.looping {
-            let net_duration: usize = self.times.iter().sum();
-            let t = (now - start_time);
-            let frame_disp: usize = t%net_duration;
-            let mut frames_past = 0;
-            let mut frame_index = 0;
-            for time in self.times.iter() {
-                frames_past += time;
-                if frames_past > frame_disp {
-                    break;
-                }
-                frame_index += 1;
-            }
-            self.frames[frame_index]
-        } else if (now-start_time) >= self.times.iter().sum() {
-            self.frames[0]
-        } else {
-            let frame_disp = (now - start_time);
-            let mut frames_past = 0;
-            let mut frame_index = 0;
-            for time in self.times.iter() {
-                frames_past += time;
-                if frames_past > frame_disp {
-                    break;
-                }
-                frame_index += 1;
-            }
-            self.frames[frame_index]
+        self.frames[frame_index(&self.times, self.looping, start_time, now)]
+    }
+}
+
+/// Which frame an animation is on, given how many frames each one is shown for
+pub fn frame_index(times: &[usize], looping: bool, start_time: usize, now: usize) -> usize {
+    let net_duration: usize = times.iter().sum();
+    let frame_disp = if looping {
+        (now - start_time) % net_duration
+    } else if (now - start_time) >= net_duration {
+        return 0;
+    } else {
+        now - start_time
+    };
+    let mut frames_past = 0;
+    let mut frame_index = 0;
+    for time in times.iter() {
+        frames_past += time;
+        if frames_past > frame_disp {
+            break;
         }
+        frame_index += 1;
     }
+    frame_index
 }
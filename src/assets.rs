@@ -0,0 +1,295 @@
+use crate::text::{read_font, FontError, FontId, Fonts};
+use crate::texture::{Texture, TextureError};
+use crate::tiled::read_tileset_file;
+use crate::tiles::Tileset;
+use fontdue::Font;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often `reload_changed` actually looks at the files
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Names an asset of type T in a `Store`
+pub struct Handle<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+// Derives would wrongly require T: Copy etc, so these are written out
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Handle<T> {}
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+impl<T> Eq for Handle<T> {}
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// Fonts live in `Fonts`, where a font's handle and its `FontId` are the same index
+impl From<Handle<Font>> for FontId {
+    fn from(handle: Handle<Font>) -> Self {
+        FontId(handle.index)
+    }
+}
+
+/// Shared assets of one type, each stored once under a name (usually the path it came from)
+pub struct Store<T> {
+    items: Vec<Rc<T>>,
+    names: HashMap<String, Handle<T>>,
+}
+impl<T> Default for Store<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> Store<T> {
+    pub fn new() -> Self {
+        Self {
+            items: vec![],
+            names: HashMap::new(),
+        }
+    }
+    /// Add an asset, replacing whatever had the same name before.
+    /// Only `get` sees the replacement; an `Rc` from before still points at the old asset,
+    /// so keep the handle and `get` the asset each time it's used if it might be replaced.
+    pub fn insert(&mut self, name: &str, item: T) -> Handle<T> {
+        if let Some(handle) = self.find(name) {
+            self.items[handle.index] = Rc::new(item);
+            return handle;
+        }
+        let handle = Handle {
+            index: self.items.len(),
+            marker: PhantomData,
+        };
+        self.items.push(Rc::new(item));
+        self.names.insert(name.to_string(), handle);
+        handle
+    }
+    pub fn find(&self, name: &str) -> Option<Handle<T>> {
+        self.names.get(name).copied()
+    }
+    pub fn get(&self, handle: Handle<T>) -> &Rc<T> {
+        &self.items[handle.index]
+    }
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Something loaded from a file that gets reloaded when the file changes
+#[derive(Clone)]
+enum Watched {
+    Texture(Handle<Texture>),
+    Font(Handle<Font>),
+    /// A tileset file and the name it's loaded under, relative to the root
+    Tileset(Handle<Tileset>, String),
+}
+
+/// Every texture, font, tileset and level a game uses.
+/// Files are loaded from under one root directory (like `content/`) and only once each,
+/// and `reload_changed` picks up edits to them while the game runs.
+/// It polls rather than watching the directory: every half second it checks the
+/// modification times of the files it has loaded, so new files aren't noticed.
+pub struct Assets<L = ()> {
+    root: PathBuf,
+    textures: Store<Texture>,
+    pub fonts: Fonts,
+    font_handles: HashMap<PathBuf, Handle<Font>>,
+    tilesets: Store<Tileset>,
+    levels: Store<L>,
+    /// Loaded files and when they were last modified
+    watched: Vec<(PathBuf, Watched, Option<SystemTime>)>,
+    last_poll: Instant,
+    /// Turn this off to stop `reload_changed` from looking at the files
+    pub hot_reload: bool,
+}
+
+impl<L> Assets<L> {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            textures: Store::new(),
+            fonts: Fonts::new(),
+            font_handles: HashMap::new(),
+            tilesets: Store::new(),
+            levels: Store::new(),
+            watched: vec![],
+            last_poll: Instant::now(),
+            hot_reload: true,
+        }
+    }
+    /// The directory files are loaded from
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// Where a file under the root is, spelled the same way however it was asked for
+    fn resolve(&self, path: &str) -> PathBuf {
+        let path = self.root.join(path);
+        if let Ok(path) = fs::canonicalize(&path) {
+            return path;
+        }
+        // Missing files still get the same name they'll have once they exist
+        match (path.parent().map(fs::canonicalize), path.file_name()) {
+            (Some(Ok(dir)), Some(file)) => dir.join(file),
+            _ => path,
+        }
+    }
+    fn watch(&mut self, path: PathBuf, what: Watched) {
+        let modified = modified(&path);
+        self.watched.push((path, what, modified));
+    }
+    /// Load an image under the root, or get the handle of the copy that's already loaded
+    pub fn load_texture(&mut self, path: &str) -> Result<Handle<Texture>, TextureError> {
+        let full = self.resolve(path);
+        let name = full.to_string_lossy().into_owned();
+        if let Some(handle) = self.textures.find(&name) {
+            return Ok(handle);
+        }
+        let handle = self.textures.insert(&name, Texture::load(&full)?);
+        self.watch(full, Watched::Texture(handle));
+        Ok(handle)
+    }
+    /**
+     * Loads an image under the root, or prints why it couldn't and uses the missing texture
+     *
+     * Params:
+     *   - path: the image, relative to the root
+     *   - width, height: how big the fallback should be, so frames cut out of it stay valid
+     *
+     * Note: The file is still watched, so fixing it while the game runs swaps the real image in
+     **/
+    pub fn load_texture_or_missing(&mut self, path: &str, width: usize, height: usize) -> Handle<Texture> {
+        match self.load_texture(path) {
+            Ok(handle) => handle,
+            Err(e) => {
+                eprintln!("{}", e);
+                let full = self.resolve(path);
+                let handle = self
+                    .textures
+                    .insert(&full.to_string_lossy(), Texture::missing(width, height));
+                self.watch(full, Watched::Texture(handle));
+                handle
+            }
+        }
+    }
+    pub fn texture(&self, handle: Handle<Texture>) -> &Rc<Texture> {
+        self.textures.get(handle)
+    }
+    /// Load a font under the root into `fonts`, or get the handle of the copy that's already loaded.
+    /// Turn the handle into a `FontId` to draw text with it.
+    pub fn load_font(&mut self, path: &str) -> Result<Handle<Font>, FontError> {
+        let full = self.resolve(path);
+        if let Some(handle) = self.font_handles.get(&full) {
+            return Ok(*handle);
+        }
+        let FontId(index) = self.fonts.add(read_font(&full)?);
+        let handle = Handle {
+            index,
+            marker: PhantomData,
+        };
+        self.font_handles.insert(full.clone(), handle);
+        self.watch(full, Watched::Font(handle));
+        Ok(handle)
+    }
+    pub fn font(&self, handle: Handle<Font>) -> &Font {
+        self.fonts.font(handle.into())
+    }
+    /// Register a tileset under a name, replacing any tileset with that name
+    pub fn add_tileset(&mut self, name: &str, tileset: Tileset) -> Handle<Tileset> {
+        self.tilesets.insert(name, tileset)
+    }
+    pub fn tileset(&self, handle: Handle<Tileset>) -> &Rc<Tileset> {
+        self.tilesets.get(handle)
+    }
+    pub fn find_tileset(&self, name: &str) -> Option<Handle<Tileset>> {
+        self.tilesets.find(name)
+    }
+    /// Reload a tileset when the tileset file it came from (relative to the root) changes
+    pub(crate) fn watch_tileset(&mut self, path: &str, handle: Handle<Tileset>) {
+        let full = self.resolve(path);
+        self.watch(full, Watched::Tileset(handle, path.to_string()));
+    }
+    /// Register a level under a name, replacing any level with that name.
+    /// Levels aren't reloaded from their files; keep a level's handle rather than its `Rc`
+    /// to see it if it's replaced.
+    pub fn add_level(&mut self, name: &str, level: L) -> Handle<L> {
+        self.levels.insert(name, level)
+    }
+    pub fn level(&self, handle: Handle<L>) -> &Rc<L> {
+        self.levels.get(handle)
+    }
+    pub fn find_level(&self, name: &str) -> Option<Handle<L>> {
+        self.levels.find(name)
+    }
+    /**
+     * Reloads any loaded files that changed on disk since they were last loaded.
+     * Call it every frame; it only checks the files every half second.
+     *
+     * Returns the paths that were reloaded
+     *
+     * Note: Textures and tilesets change in place, so every sprite and tilemap using them
+     * shows the new version. Files that fail to reload are reported and left as they were.
+     **/
+    pub fn reload_changed(&mut self) -> Vec<PathBuf> {
+        let mut reloaded = vec![];
+        if !self.hot_reload || self.last_poll.elapsed() < POLL_INTERVAL {
+            return reloaded;
+        }
+        self.last_poll = Instant::now();
+        let mut changed = vec![];
+        for (path, what, last_modified) in self.watched.iter_mut() {
+            let now_modified = modified(path);
+            if now_modified.is_none() || now_modified == *last_modified {
+                continue;
+            }
+            *last_modified = now_modified;
+            changed.push((path.clone(), what.clone()));
+        }
+        for (path, what) in changed {
+            let result = match what {
+                Watched::Texture(handle) => self.textures.get(handle).reload(&path).map_err(|e| e.to_string()),
+                Watched::Font(handle) => read_font(&path)
+                    .map(|font| self.fonts.replace(handle.into(), font))
+                    .map_err(|e| e.to_string()),
+                Watched::Tileset(handle, name) => read_tileset_file(self, &name)
+                    .map_err(|e| e.to_string())
+                    .and_then(|tileset| {
+                        self.tileset(handle)
+                            .replace(tileset)
+                            .map_err(|e| format!("can't reload {}: {}", path.display(), e))
+                    }),
+            };
+            match result {
+                Ok(()) => reloaded.push(path),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        reloaded
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,296 @@
+use crate::animation::Animation;
+use crate::texture::{load_image, missing_image, Texture, TextureError};
+use crate::types::Rect;
+use image::{imageops, RgbaImage};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Empty pixels between packed images, so filtering never bleeds one into the next
+const ATLAS_PADDING: u32 = 1;
+
+/// A frame to name once the image it's in has been packed
+struct FrameDef {
+    image: String,
+    name: String,
+    /// Where the frame is within its image
+    rect: Rect,
+}
+
+/// Collects images to pack into one texture.
+/// Each image gets a frame with its own name, and more frames can be named inside it.
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    frames: Vec<FrameDef>,
+    /// Textures whose pixels `Atlas::refresh` copies in again, by their index in `images`
+    shared: Vec<(usize, Rc<Texture>)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            images: vec![],
+            frames: vec![],
+            shared: vec![],
+        }
+    }
+    /// Panic if an image or frame is already called `name`, since only one of them could be found
+    fn check_unused(&self, name: &str) {
+        if self.images.iter().any(|(n, _)| n == name) || self.frames.iter().any(|f| f.name == name) {
+            panic!("There's already a frame called {} in the atlas", name);
+        }
+    }
+    /// Add an image; the whole thing becomes a frame called `name`
+    pub fn add(&mut self, name: &str, image: RgbaImage) {
+        self.check_unused(name);
+        self.images.push((name.to_string(), image));
+    }
+    /// Add a texture, e.g. one recolored at runtime
+    pub fn add_texture(&mut self, name: &str, texture: &Texture) {
+        self.add(name, texture.to_image());
+    }
+    /// Add a texture that can change later, like one loaded through `Assets`;
+    /// `Atlas::refresh` picks up its new pixels
+    pub fn add_shared(&mut self, name: &str, texture: &Rc<Texture>) {
+        self.shared.push((self.images.len(), Rc::clone(texture)));
+        self.add_texture(name, texture);
+    }
+    pub fn add_file(&mut self, name: &str, path: &Path) -> Result<(), TextureError> {
+        self.add(name, load_image(path)?);
+        Ok(())
+    }
+    /// Add an image file, or print why it couldn't be loaded and add a missing texture instead
+    pub fn add_file_or_missing(&mut self, name: &str, path: &Path, width: usize, height: usize) {
+        if let Err(e) = self.add_file(name, path) {
+            eprintln!("{}", e);
+            self.add(name, missing_image(width, height));
+        }
+    }
+    /// Name a frame inside an image that's been added
+    pub fn define(&mut self, image: &str, name: &str, rect: Rect) {
+        self.check_unused(name);
+        self.frames.push(FrameDef {
+            image: image.to_string(),
+            name: name.to_string(),
+            rect,
+        });
+    }
+    /**
+     * Names the frames of a sprite sheet laid out in a grid
+     *
+     * Params:
+     *   - image: the name the sheet was added with
+     *   - cell_w, cell_h: how far apart the frames are
+     *   - frame_w, frame_h: how big each frame is, from its cell's top left corner
+     *
+     * Note: The frames are called `<image>/0`, `<image>/1` and so on, left to right then top to bottom.
+     * Panics if a cell is empty or if the image hasn't been added yet.
+     **/
+    pub fn define_grid(&mut self, image: &str, cell_w: u16, cell_h: u16, frame_w: u16, frame_h: u16) {
+        assert!(cell_w > 0 && cell_h > 0, "Grid cells in {} can't be empty", image);
+        let (w, h) = match self.images.iter().find(|(n, _)| n == image) {
+            Some((_, img)) => img.dimensions(),
+            None => panic!("No image called {} in the atlas", image),
+        };
+        let columns = w / cell_w as u32;
+        let rows = h / cell_h as u32;
+        for i in 0..(columns * rows) {
+            let rect = Rect {
+                x: ((i % columns) * cell_w as u32) as i32,
+                y: ((i / columns) * cell_h as u32) as i32,
+                w: frame_w,
+                h: frame_h,
+            };
+            self.define(image, &format!("{}/{}", image, i), rect);
+        }
+    }
+    /// Pack every image into one texture, tallest first on shelves
+    pub fn build(self) -> Atlas {
+        let area: u32 = self
+            .images
+            .iter()
+            .map(|(_, img)| (img.width() + ATLAS_PADDING) * (img.height() + ATLAS_PADDING))
+            .sum();
+        let widest = self.images.iter().map(|(_, img)| img.width()).max().unwrap_or(0);
+        // Aim for a roughly square texture, but never narrower than the widest image
+        let width = widest.max((area as f32).sqrt().ceil() as u32);
+
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].1.height()));
+        let mut placed = vec![(0, 0); self.images.len()];
+        let (mut x, mut y, mut shelf_h) = (0, 0, 0);
+        for &i in order.iter() {
+            let img = &self.images[i].1;
+            if x > 0 && x + img.width() > width {
+                x = 0;
+                y += shelf_h + ATLAS_PADDING;
+                shelf_h = 0;
+            }
+            placed[i] = (x, y);
+            x += img.width() + ATLAS_PADDING;
+            shelf_h = shelf_h.max(img.height());
+        }
+        let height = y + shelf_h;
+
+        let mut packed = RgbaImage::new(width.max(1), height.max(1));
+        let mut frames = HashMap::new();
+        for ((name, img), &(x, y)) in self.images.iter().zip(placed.iter()) {
+            imageops::replace(&mut packed, img, x, y);
+            let rect = Rect {
+                x: x as i32,
+                y: y as i32,
+                w: img.width() as u16,
+                h: img.height() as u16,
+            };
+            frames.insert(name.clone(), rect);
+        }
+        for def in self.frames.iter() {
+            let image = match frames.get(&def.image) {
+                Some(&rect) => rect,
+                None => panic!("Frame {} is in {}, but no image called that was added", def.name, def.image),
+            };
+            assert!(
+                def.rect.x >= 0
+                    && def.rect.y >= 0
+                    && def.rect.x + def.rect.w as i32 <= image.w as i32
+                    && def.rect.y + def.rect.h as i32 <= image.h as i32,
+                "Frame {} doesn't fit in {}",
+                def.name,
+                def.image
+            );
+            let rect = Rect {
+                x: image.x + def.rect.x,
+                y: image.y + def.rect.y,
+                ..def.rect
+            };
+            frames.insert(def.name.clone(), rect);
+        }
+        let shared = self
+            .shared
+            .into_iter()
+            .map(|(i, texture)| (texture, placed[i]))
+            .collect();
+        Atlas {
+            texture: Rc::new(Texture::new(packed)),
+            frames,
+            shared,
+        }
+    }
+}
+
+/// Lots of images packed into one texture, with their frames looked up by name
+pub struct Atlas {
+    texture: Rc<Texture>,
+    frames: HashMap<String, Rect>,
+    /// Shared textures and where they were packed
+    shared: Vec<(Rc<Texture>, (u32, u32))>,
+}
+
+impl Atlas {
+    pub fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
+    /**
+     * Copies the current pixels of every texture added with `add_shared` back into the atlas,
+     * e.g. after `Assets::reload_changed` reloaded some of them
+     *
+     * Note: The atlas texture changes in place, so everything drawing from it sees the new pixels.
+     * Reloading never resizes a texture, so everything still fits where it was packed.
+     **/
+    pub fn refresh(&self) {
+        for (texture, (x, y)) in self.shared.iter() {
+            self.texture.paste(texture, *x as usize, *y as usize);
+        }
+    }
+    pub fn get(&self, name: &str) -> Option<Rect> {
+        self.frames.get(name).copied()
+    }
+    /// The frame with this name, panicking if there isn't one
+    pub fn frame(&self, name: &str) -> Rect {
+        match self.get(name) {
+            Some(rect) => rect,
+            None => panic!("No frame called {} in the atlas", name),
+        }
+    }
+    /// An animation through named frames, each shown for the given number of frames
+    pub fn animation(&self, names: &[&str], times: Vec<usize>, looping: bool) -> Animation {
+        assert_eq!(names.len(), times.len());
+        Animation {
+            frames: names.iter().map(|name| self.frame(name)).collect(),
+            times,
+            looping,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> AtlasBuilder {
+        let mut atlas = AtlasBuilder::new();
+        atlas.add("sheet", RgbaImage::new(8, 4));
+        atlas.add("dot", RgbaImage::new(1, 1));
+        atlas
+    }
+
+    #[test]
+    fn names_frames_inside_images() {
+        let mut atlas = builder();
+        atlas.define_grid("sheet", 4, 4, 3, 4);
+        let atlas = atlas.build();
+        let sheet = atlas.frame("sheet");
+        assert_eq!((sheet.w, sheet.h), (8, 4));
+        let second = atlas.frame("sheet/1");
+        assert_eq!((second.x, second.y), (sheet.x + 4, sheet.y));
+        assert_eq!((second.w, second.h), (3, 4));
+        assert!(atlas.get("sheet/2").is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "already a frame called dot")]
+    fn refuses_reused_image_names() {
+        builder().add("dot", RgbaImage::new(2, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "already a frame called sheet/0")]
+    fn refuses_reused_frame_names() {
+        let mut atlas = builder();
+        atlas.define(
+            "sheet",
+            "sheet/0",
+            Rect {
+                x: 0,
+                y: 0,
+                w: 1,
+                h: 1,
+            },
+        );
+        atlas.define_grid("sheet", 4, 4, 4, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be empty")]
+    fn refuses_empty_grid_cells() {
+        builder().define_grid("sheet", 0, 4, 4, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "no image called that")]
+    fn refuses_frames_in_missing_images() {
+        let mut atlas = builder();
+        atlas.define(
+            "shet",
+            "typo",
+            Rect {
+                x: 0,
+                y: 0,
+                w: 1,
+                h: 1,
+            },
+        );
+        atlas.build();
+    }
+}
@@ -1,10 +1,7 @@
-use fontdue::{
-    layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle},
-    Font,
-};
+use fontdue::layout::{HorizontalAlign, VerticalAlign};
 use pixels::{Pixels, SurfaceTexture};
 use rand::Rng;
-use std::{fs::read, path::Path, rc::Rc, time::Instant};
+use std::{path::Path, rc::Rc, time::Instant};
 use winit::dpi::LogicalSize;
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
@@ -12,7 +9,7 @@ use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
 use Unit2_2D::{
-    animation::*, collision::*, health::*, screen::Screen, sprite::*, text::*, texture::Texture, tiles::*,
+    animation::*, assets::*, canvas::*, collision::*, health::*, screen::Screen, sprite::*, text::*, tiled::*, tiles::*,
     types::*,
 };
 
@@ -35,123 +32,100 @@ struct GameState {
     health: HealthStatus,
     contacts: Vec<Contact>,
     immunities: Vec<isize>,
-    fonts: Fonts,
+    assets: Assets,
 }
 
 const WIDTH: usize = 320;
 const HEIGHT: usize = 240;
 const DEPTH: usize = 4;
 const DT: f64 = 1.0 / 60.0;
+const GOLDEN_TOLERANCE: u8 = 2;
 
 fn main() {
-    let event_loop = EventLoop::new();
-    let mut input = WinitInputHelper::new();
-    let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        WindowBuilder::new()
-            .with_title("Runner Game")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .with_resizable(false)
-            .build(&event_loop)
-            .unwrap()
-    };
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap()
-    };
-
-    let font: &[u8] = &read(Path::new("content/monogram_font.ttf")).unwrap();
-    let fonts = [Font::from_bytes(font, fontdue::FontSettings::default()).unwrap()];
+    let mut assets = Assets::new(Path::new("content"));
+    // Without the font the game still runs, just without any text
+    if let Err(e) = assets.load_font("monogram_font.ttf") {
+        eprintln!("{}", e);
+    }
 
     // TODO: Once we find the texture we want to use replace this path and delete the current placeholder file
-    let tex = Rc::new(Texture::with_file(Path::new("content/penguin.png")));
-    let tile_tex = Rc::new(Texture::with_file(Path::new("content/Background.png")));
-    let health_tex = Rc::new(Texture::with_file(Path::new("content/Heart.png")));
-    let obs_tex = Rc::new(Texture::with_file(Path::new("content/IceTileset.png")));
-    let tileset = Rc::new(Tileset::new(
-        vec![
-            Tile {
-                solid: false,
-                collide: Effect::Nothing,
-            },
-            Tile {
-                solid: false,
-                collide: Effect::Nothing,
-            },
-            Tile {
-                solid: true,
-                collide: Effect::Nothing,
-            },
-            Tile {
-                solid: true,
-                collide: Effect::Nothing,
-            },
-        ],
-        &tile_tex,
-    ));
-
-    let obs_set = Rc::new(Tileset::new(
-        //ice 1
-        vec![
-            //0:active ice
-            Tile {
-                solid: false,
-                collide: Effect::Speedup(1),
-            },
-            //1:used ice
-            Tile {
-                solid: false,
-                collide: Effect::Nothing,
-            },
-            //2: active rock
-            Tile {
-                solid: false,
-                collide: Effect::Hurt(1),
-            },
-            //3: tree
-            Tile {
-                solid: false,
-                collide: Effect::Hurt(1),
-            },
-            //4: ground
-            Tile {
-                solid: false,
-                collide: Effect::Nothing,
-            },
-            //5: wall
-            Tile {
-                solid: true,
-                collide: Effect::Nothing,
-            },
-            //6: nothing
-            Tile {
-                solid: false,
-                collide: Effect::Nothing,
-            },
-        ],
-        &obs_tex,
-    ));
-    let obstacle_map: Vec<Tilemap> = vec![
-        Tilemap::new(Vec2i(TILE_SZ as i32, 0), (8, 5), &obs_set, vec![6; 40]),
-        Tilemap::new(
-            Vec2i(TILE_SZ as i32, 5 * TILE_SZ as i32),
-            (8, 5),
-            &obs_set,
-            vec![6; 40],
-        ),
-        Tilemap::new(
-            Vec2i(TILE_SZ as i32, 10 * TILE_SZ as i32),
-            (8, 5),
-            &obs_set,
-            vec![6; 40],
-        ),
-        Tilemap::new(
-            Vec2i(TILE_SZ as i32, 15 * TILE_SZ as i32),
-            (8, 5),
-            &obs_set,
-            vec![6;40])];
+    let tex = assets.load_texture_or_missing("penguin.png", 64, 32);
+    let health_tex = assets.load_texture_or_missing("Heart.png", 85, 17);
+    let obs_tex = assets.load_texture_or_missing("IceTileset.png", 128, 96);
+    // The scrolling background is a Tiled map
+    let mut background = TiledMap::load(&mut assets, "maps/background.tmj").unwrap_or_else(|e| panic!("{}", e));
+
+    let obs_set = assets.add_tileset(
+        "ice",
+        Tileset::new(
+            //ice 1
+            vec![
+                //0:active ice
+                Tile {
+                    solid: false,
+                    collide: Effect::Speedup(1),
+                },
+                //1:used ice
+                Tile {
+                    solid: false,
+                    collide: Effect::Nothing,
+                },
+                //2: active rock
+                Tile {
+                    solid: false,
+                    collide: Effect::Hurt(1),
+                },
+                //3: tree
+                Tile {
+                    solid: false,
+                    collide: Effect::Hurt(1),
+                },
+                //4: ground
+                Tile {
+                    solid: false,
+                    collide: Effect::Nothing,
+                },
+                //5: wall
+                Tile {
+                    solid: true,
+                    collide: Effect::Nothing,
+                },
+                //6: nothing
+                Tile {
+                    solid: false,
+                    collide: Effect::Nothing,
+                },
+                //7-9: active ice with a glint going across, only drawn by tile 0's animation
+                Tile {
+                    solid: false,
+                    collide: Effect::Speedup(1),
+                },
+                Tile {
+                    solid: false,
+                    collide: Effect::Speedup(1),
+                },
+                Tile {
+                    solid: false,
+                    collide: Effect::Speedup(1),
+                },
+            ],
+            assets.texture(obs_tex),
+        )
+        // Every so often a glint runs across the active ice
+        .with_animation(0, vec![0, 7, 8, 9], vec![60, 4, 4, 4]),
+    );
+    // Four 8x5 strips of obstacles stacked on top of each other, one tile in from the left
+    let (tw, th) = assets.tileset(obs_set).tile_size();
+    let obstacle_map: Vec<Tilemap> = (0..4)
+        .map(|i| {
+            Tilemap::new(
+                Vec2i(tw as i32, (i * 5 * th) as i32),
+                (8, 5),
+                assets.tileset(obs_set),
+                vec![6; 40],
+            )
+        })
+        .collect();
 
     let animations: Vec<Animation> = vec![
         Animation {
@@ -205,7 +179,7 @@ fn main() {
     let mut state = GameState {
         mode: GameMode::Title,
         player: Sprite::new(
-            &tex,
+            assets.texture(tex),
             Rect {
                 x: 0,
                 y: 0,
@@ -222,20 +196,10 @@ fn main() {
         player_velocity: 0.0,
         scroll_speed: 2,
         scroll_timer: 180,
-        map: Tilemap::new(
-            Vec2i(0, 0),
-            (10, 10),
-            &tileset,
-            vec![
-                2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0,
-                0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0,
-                0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 0, 0,
-                0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2,
-            ],
-        ),
+        map: background.layers.remove(0).tilemap,
         obstacle_maps: obstacle_map,
         health: HealthStatus {
-            image: Rc::clone(&health_tex),
+            image: Rc::clone(assets.texture(health_tex)),
             lives: 3,
             frame: Rect {
                 x: 0,
@@ -248,8 +212,33 @@ fn main() {
         },
         contacts: vec![],
         immunities: vec![0, 0],
-        fonts: Fonts::new(fonts),
+        assets,
+    };
+    // Headless mode for CI: render each screen offscreen and check it against the goldens
+    if let Some(dir) = golden_dir() {
+        if !check_goldens(&mut state, &animations, Path::new(&dir), updating_goldens()) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let event_loop = EventLoop::new();
+    let mut input = WinitInputHelper::new();
+    let window = {
+        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+        WindowBuilder::new()
+            .with_title("Runner Game")
+            .with_inner_size(size)
+            .with_min_inner_size(size)
+            .with_resizable(false)
+            .build(&event_loop)
+            .unwrap()
+    };
+    let mut pixels = {
+        let window_size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap()
     };
+
     // How many frames have we simulated
     let mut frame_count: usize = 0;
     // How many unsimulated frames have we saved up
@@ -288,6 +277,8 @@ fn main() {
                 pixels.resize(size.width, size.height);
             }
         }
+        // Pick up any art that changed on disk
+        state.assets.reload_changed();
         // And the simulation "consumes" it
         while available_time >= DT {
             // Eat up one frame worth of time
@@ -305,6 +296,52 @@ fn main() {
     });
 }
 
+/// The directory passed with `--golden <dir>`, if any
+fn golden_dir() -> Option<String> {
+    std::env::args().skip_while(|a| a != "--golden").nth(1)
+}
+
+/// With --update-goldens the screens are saved as the new goldens instead of being checked
+fn updating_goldens() -> bool {
+    std::env::args().any(|a| a == "--update-goldens")
+}
+
+/**
+ * Draws each game mode into a headless canvas and compares it to the golden images,
+ * or saves them as the goldens if `update` is set
+ *
+ * Params:
+ *   - state: the freshly set up game state
+ *   - animations: the player animations
+ *   - dir: directory holding `<mode>.png` goldens
+ *   - update: write the goldens instead of checking them
+ *
+ * Returns whether every screen matched (or was saved)
+ **/
+fn check_goldens(state: &mut GameState, animations: &[Animation], dir: &Path, update: bool) -> bool {
+    let mut ok = true;
+    for (name, mode) in vec![
+        ("title", GameMode::Title),
+        ("playing", GameMode::Playing),
+        ("game_over", GameMode::GameOver),
+    ] {
+        state.mode = mode;
+        let mut canvas = Canvas::new(WIDTH, HEIGHT);
+        draw_game(state, &mut canvas.screen(Vec2i(0, 0)), 0, animations);
+        let path = dir.join(format!("{}.png", name));
+        let result = if update {
+            update_golden(&canvas, &path)
+        } else {
+            check_golden(&canvas, &path, GOLDEN_TOLERANCE)
+        };
+        if let Err(e) = result {
+            eprintln!("{}: {}", name, e);
+            ok = false;
+        }
+    }
+    ok
+}
+
 fn draw_game(state: &mut GameState, screen: &mut Screen, frame: usize, animations: &[Animation]) {
     // Note: I had to make state mut to change the rasterized hashmap as needed
     // Call screen's drawing methods to render the game state
@@ -313,7 +350,7 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, frame: usize, animation
     match state.mode {
         GameMode::Title => {
             // draws menu screen
-            state.map.draw(screen);
+            state.map.draw(screen, frame);
 
             let w = WIDTH as i32;
             let h = HEIGHT as i32;
@@ -327,46 +364,35 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, frame: usize, animation
             screen.rect(menu_rect, Rgba(20, 0, 100, 255));
             screen.empty_rect(menu_rect, 4, Rgba(200, 220, 255, 255));
 
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 6) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("PENGUIN\nSLEDDING", 45.0, 0),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "PENGUIN\nSLEDDING",
+                Rect {
+                    x: w / 6,
+                    y: h / 6,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 6) as u16,
+                },
+                &TextFormat::new(45.0, Rgba(255, 255, 255, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(255, 255, 255, 255),
-            );
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 2) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("Press ENTER to start", 20.0, 0),
-            );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(255, 255, 255, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "Press ENTER to start",
+                Rect {
+                    x: w / 6,
+                    y: h / 2,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 2) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(255, 255, 255, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
         }
         GameMode::Playing => {
-            state.map.draw(screen);
+            state.map.draw(screen, frame);
             for map in state.obstacle_maps.iter() {
-                map.draw(screen);
+                map.draw(screen, frame);
             }
 
             state.player.frame = animations[state.player.animation].current_frame(state.player.animation_start, frame);
@@ -375,7 +401,7 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, frame: usize, animation
         }
         GameMode::GameOver => {
             // draws game over screen
-            state.map.draw(screen);
+            state.map.draw(screen, frame);
 
             let w = WIDTH as i32;
             let h = HEIGHT as i32;
@@ -389,40 +415,29 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, frame: usize, animation
             screen.rect(menu_rect, Rgba(20, 0, 100, 255));
             screen.empty_rect(menu_rect, 4, Rgba(200, 220, 255, 255));
 
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 6) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("GAME\nOVER", 45.0, 0),
-            );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(255, 255, 255, 255),
-            );
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 2) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("Press ENTER to play again", 20.0, 0),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "GAME\nOVER",
+                Rect {
+                    x: w / 6,
+                    y: h / 6,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 6) as u16,
+                },
+                &TextFormat::new(45.0, Rgba(255, 255, 255, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(255, 255, 255, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "Press ENTER to play again",
+                Rect {
+                    x: w / 6,
+                    y: h / 2,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 2) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(255, 255, 255, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
         }
     }
@@ -435,8 +450,8 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, frame: usize, animation
  */
 fn update_obstacles(state: &mut GameState) {
     let mut rng = rand::thread_rng();
-    let height: i32 = TILE_SZ as i32 * 5;
     for obs_map in state.obstacle_maps.iter_mut() {
+        let height = obs_map.pixel_size().1 as i32;
         obs_map.position.1 -= state.scroll_speed as i32;
         //print!("\n pos: {}", obs_map.position.1);
         if obs_map.position.1 + height <= 0 {
@@ -465,7 +480,7 @@ fn update_obstacles(state: &mut GameState) {
 
 fn update_tiles(state: &mut GameState) {
     state.map.position.1 -= state.scroll_speed as i32;
-    if state.map.position.1.abs() >= TILE_SZ as i32 {
+    if state.map.position.1.abs() >= state.map.tile_size().1 as i32 {
         state.map.position.1 = 0;
     }
 }
@@ -1,11 +1,8 @@
-use fontdue::{
-    layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle},
-    Font,
-};
+use fontdue::layout::{HorizontalAlign, VerticalAlign};
 use image::imageops::vertical_gradient;
 use pixels::{Pixels, SurfaceTexture};
 use rand::Rng;
-use std::{fs::read, path::Path, rc::Rc, time::Instant};
+use std::{path::Path, rc::Rc, time::Instant};
 use winit::dpi::LogicalSize;
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
@@ -13,12 +10,10 @@ use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
 use Unit2_2D::{
-    animation::*, collision::*, health::*, screen::Screen, sprite::*, text::*, texture::Texture, tiles::*,
-    types::*,
+    animation::*, assets::*, atlas::*, canvas::*, collision::*, dialogue::*, health::*, level::*, markup::*,
+    screen::{Screen, Viewport}, sprite::*, text::*, tiles::*, types::*,
 };
 
-type Level = (Vec<Tilemap>, Vec<Sprite>, usize);
-
 enum GameMode {
     Title,
     Map,
@@ -26,6 +21,7 @@ enum GameMode {
     FightChoice,
     GameOver,
     Win,
+    Dialogue,
 }
 
 struct GameState {
@@ -39,298 +35,80 @@ struct GameState {
     choice_frame: usize,
     contacts: Vec<Contact>,
     window: Vec2i,
-    level: usize,
+    level: Handle<Level>,
+    /// Where the game starts over from
+    first_level: Handle<Level>,
+    /// How many floors down the player is, counting from 0
+    floor: usize,
     passed: bool,
-    fonts: Fonts,
-
+    assets: Assets<Level>,
+    icons: Icons,
+    fight_message: String,
+    dialogue: Dialogue,
+    /// Where to go once the dialogue is over
+    after_dialogue: GameMode,
 }
 
 const WIDTH: usize = 320;
 const HEIGHT: usize = 256;
 const DEPTH: usize = 4;
 const DT: f64 = 1.0 / 60.0;
+const GOLDEN_TOLERANCE: u8 = 2;
 
 fn main() {
-    let event_loop = EventLoop::new();
-    let mut input = WinitInputHelper::new();
-    let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        WindowBuilder::new()
-            .with_title("Title")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .with_resizable(false)
-            .build(&event_loop)
-            .unwrap()
-    };
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap()
-    };
-
-    let font: &[u8] = &read(Path::new("content/monogram_font.ttf")).unwrap();
-    let fonts = [Font::from_bytes(font, fontdue::FontSettings::default()).unwrap()];
+    let mut assets = Assets::new(Path::new("content"));
+    // Without the font the game still runs, just without any text
+    if let Err(e) = assets.load_font("monogram_font.ttf") {
+        eprintln!("{}", e);
+    }
 
-    // TODO: Once we find the texture we want to use replace this path and delete the current placeholder file
-    let tex = Rc::new(Texture::with_file(Path::new("content/dino.png")));
-    let enemy_tex_r = Rc::new(Texture::with_file(Path::new("content/dinor.png")));
-    let enemy_tex_g = Rc::new(Texture::with_file(Path::new("content/dinog.png")));
-    let enemy_tex_y = Rc::new(Texture::with_file(Path::new("content/dinoy.png")));
-    let level_tex = Rc::new(Texture::with_file(Path::new("content/dungeon.png")));
-    let health_tex = Rc::new(Texture::with_file(Path::new("content/Heart.png")));
-    let tileset = Rc::new(Tileset::new(
-        {
-            (0..64)
-                .map(|i| {
-                    if i == 0 || i == 2 || i == 1 || i == 30 || i == 16 || i == 17 || i == 18 || i == 36 || i == 37 || i == 38 || i == 44 || i == 46 || i == 52 || i ==53 || i == 54 || i == 43 {
-                        Tile {
-                            solid: true,
-                            collide: Effect::Nothing,
-                        }
-                    } else {
-                        Tile {
-                            solid: false,
-                            collide: Effect::Nothing,
-                        }
-                    }
-                })
-                .collect()
+    // The dinos and hearts all go in one texture
+    let mut atlas = AtlasBuilder::new();
+    // Loaded through the assets so edits to them get refreshed into the atlas
+    for name in ["dino", "dinor", "dinog", "dinoy"].iter() {
+        let texture = assets.load_texture_or_missing(&format!("{}.png", name), 576, 24);
+        atlas.add_shared(name, assets.texture(texture));
+    }
+    let heart = assets.load_texture_or_missing("Heart.png", 85, 17);
+    atlas.add_shared("Heart", assets.texture(heart));
+    // The player's frames are a bit narrower than the enemies'
+    atlas.define_grid("dino", 24, 24, 20, 24);
+    for name in ["dinor", "dinog", "dinoy"].iter() {
+        atlas.define_grid(name, 24, 24, 24, 24);
+    }
+    atlas.define(
+        "Heart",
+        "heart",
+        Rect {
+            x: 0,
+            y: 0,
+            w: 16,
+            h: 16,
         },
-        &level_tex,
-    ));
-
+    );
+    let atlas = atlas.build();
+    let mut icons = Icons::new();
+    icons.add("heart", atlas.texture(), atlas.frame("heart"));
     let animations: Vec<Animation> = vec![
-        Animation {
-            frames: vec![Rect {
-                x: 24,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Rect {
-                x: 48,
-                y: 0,
-                w: 20,
-                h: 24,
-            },],
-            times: vec![5, 5],
-            looping: true,
-        },
-        Animation {
-            frames: vec![Rect {
-                x: 96,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Rect {
-                x: 120,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Rect {
-                x: 144,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Rect {
-                x: 168,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Rect {
-                x: 192,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Rect {
-                x: 216,
-                y: 0,
-                w: 20,
-                h: 24,
-            },],
-            times: vec![3, 3, 3, 3, 3, 3],
-            looping: true,
-        },
-    ];
-
-    let levels: Vec<Level> = vec![
-        (
-            vec![
-                Tilemap::new(
-                    Vec2i(0, 0),
-                    (10, 9),
-                    &tileset,
-                    vec![
-                        0, 1, 1, 1, 1, 19, 1, 1, 1, 2, 0, 32, 33, 33, 33, 41, 33, 33, 34, 2, 
-                        0, 40, 36, 38, 41, 41, 41, 41, 42, 2, 0, 40, 44, 46, 41, 41, 43, 41, 42, 2, 
-                        0, 40, 44, 46, 41, 41, 41, 41, 42, 2, 0, 40, 52, 54, 41, 41, 41, 43, 42, 2, 
-                        0, 40, 41, 41, 41, 41, 41, 41, 42, 2, 16, 17, 17, 17, 40, 17, 17, 17, 17, 18, 
-                        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    ],
-                ),
-                Tilemap::new(
-                    Vec2i(0, 0),
-                    (10, 2),
-                    &tileset,
-                    vec![
-                        30, 30, 22, 30, 30, 5, 30, 30, 30, 30, 30, 14, 30, 30, 30, 13, 30, 23, 30, 30,
-                    ],
-                ),
-            ],
-            vec![Sprite::new(
-                &enemy_tex_r,
-                Rect {
-                    x: 0,
-                    y: 0,
-                    w: 24,
-                    h: 24,
-                },
-                Vec2i(164, 32),
-                true,
-                0,
-                0,
-                AnimationState::Nothing,
-                Effect::Fight,
-            )],
-            3,
-        ),
-        (
-            vec![
-                Tilemap::new(
-                    Vec2i(0, 0),
-                    (10, 13),
-                    &tileset,
-                    vec![
-                        0, 1, 1, 1, 1, 19, 1, 1, 1, 2, 0, 32, 33, 33, 33, 41, 33, 33, 34, 2, 
-                        0, 40, 41, 41, 41, 41, 41, 41, 42, 2, 0, 40, 41, 41, 41, 41, 41, 36, 38, 2, 
-                        0, 40, 43, 41, 36, 37, 37, 63, 46, 2, 0, 40, 41, 41, 52, 53, 53, 53, 54, 2, 
-                        0, 40, 41, 41, 41, 41, 41, 41, 42, 2, 16, 17, 17, 17, 40, 17, 17, 17, 17, 18, 30,
-                        30, 30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 0, 40, 2, 30, 30, 30, 30, 30,
-                        30, 30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 0, 40, 2, 30, 30, 30, 30, 30,
-                        30, 30, 30, 30, 30, 30, 30, 30, 30,
-                    ],
-                ),
-                Tilemap::new(
-                    Vec2i(0, 0),
-                    (10, 2),
-                    &tileset,
-                    vec![
-                        30, 22, 30, 30, 22, 5, 30, 30, 22, 30,
-                        30, 15, 30, 30, 30, 13, 14, 30, 15, 30,
-                    ],
-                ),
-            ],
-            vec![Sprite::new(
-                &enemy_tex_g,
-                Rect {
-                    x: 0,
-                    y: 0,
-                    w: 24,
-                    h: 24,
-                },
-                Vec2i(164, 32),
-                true,
-                0,
-                0,
-                AnimationState::Nothing,
-                Effect::Fight,
-            )],
-            4,
-        ),
-        (
-            vec![
-                Tilemap::new(
-                    Vec2i(0, 0),
-                    (10, 13),
-                    &tileset,
-                    vec![
-                        0, 1, 1, 1, 1, 19, 1, 1, 1, 2, 0, 32, 33, 33, 33, 41, 33, 33, 34, 2, 
-                        0, 40, 41, 41, 41, 41, 41, 41, 42, 2, 0, 40, 36, 37, 38, 41, 41, 41, 42, 2, 
-                        0, 40, 44, 45, 62, 38, 41, 41, 42, 2, 0, 40, 52, 53, 53, 54, 41, 43, 42, 2, 
-                        0, 40, 41, 41, 41, 41, 41, 41, 42, 2, 16, 17, 17, 17, 40, 17, 17, 17, 17, 18, 30,
-                        30, 30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 0, 40, 2, 30, 30, 30, 30, 30,
-                        30, 30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 0, 40, 2, 30, 30, 30, 30, 30,
-                        30, 30, 30, 30, 30, 30, 30, 30, 30,
-                    ],
-                ),
-                Tilemap::new(
-                    Vec2i(0, 0),
-                    (10, 2),
-                    &tileset,
-                    vec![
-                        30, 30, 22, 30, 30, 5, 30, 30, 30, 30, 
-                        30, 30, 6, 7, 30, 13, 30, 30, 7, 30,
-                    ],
-                ),
-            ],
-            vec![Sprite::new(
-                &enemy_tex_y,
-                Rect {
-                    x: 0,
-                    y: 0,
-                    w: 24,
-                    h: 24,
-                },
-                Vec2i(164, 32),
-                true,
-                0,
-                0,
-                AnimationState::Nothing,
-                Effect::Fight,
-            )],
-            5,
-        ),
-        (
-            vec![Tilemap::new(
-                Vec2i(0, 0),
-                (10, 13),
-                &tileset,
-                vec![
-                    30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
-                    30, 30, 0, 1, 1, 1, 2, 30, 30, 30, 30, 30, 0, 32, 33, 33, 2, 30, 30, 30, 30,
-                    30, 0, 40, 41, 41, 2, 30, 30, 30, 30, 30, 0, 40, 41, 41, 2, 30, 30, 30, 30, 30,
-                    0, 40, 41, 41, 2, 30, 30, 30, 30, 30, 16, 17, 40, 17, 18, 30, 30, 30, 30, 30,
-                    30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30,
-                    0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 0, 40, 2, 30, 30, 30, 30, 30, 30, 30, 30,
-                    30, 30, 30, 30, 30, 30,
-                ],
-            )],
-            vec![Sprite::new(
-                &level_tex,
-                Rect {
-                    x: 160,
-                    y: 64,
-                    w: 32,
-                    h: 32,
-                },
-                Vec2i(128, 128),
-                true,
-                0,
-                0,
-                AnimationState::Nothing,
-                Effect::Win,
-            )],
-            0,
+        atlas.animation(&["dino/1", "dino/2"], vec![5, 5], true),
+        atlas.animation(
+            &["dino/4", "dino/5", "dino/6", "dino/7", "dino/8", "dino/9"],
+            vec![3, 3, 3, 3, 3, 3],
+            true,
         ),
     ];
 
+    // The floors are level files, loaded by following the exits down from the first one
+    let first_level =
+        Level::load_all(&mut assets, &atlas, "levels", "floor1").unwrap_or_else(|e| panic!("{}", e));
+    let start = Rc::clone(assets.level(first_level));
+
     let mut state = GameState {
         mode: GameMode::Title,
         player: Sprite::new(
-            &tex,
-            Rect {
-                x: 0,
-                y: 0,
-                w: 20,
-                h: 24,
-            },
-            Vec2i(136, 224),
+            atlas.texture(),
+            atlas.frame("dino/0"),
+            start.data.player_spawn,
             true,
             0,
             0,
@@ -338,26 +116,16 @@ fn main() {
             Effect::Nothing
         ),
         health: HealthStatus {
-            image: Rc::clone(&health_tex),
+            image: Rc::clone(atlas.texture()),
             lives: 5,
-            frame: Rect {
-                x: 0,
-                y: 0,
-                w: 16,
-                h: 16,
-            },
+            frame: atlas.frame("heart"),
             start: Vec2i(32, 56),
             spacing: 18,
         },
         enemy_health: HealthStatus {
-            image: Rc::clone(&health_tex),
-            lives: levels[0].2,
-            frame: Rect {
-                x: 0,
-                y: 0,
-                w: 16,
-                h: 16,
-            },
+            image: Rc::clone(atlas.texture()),
+            lives: enemy_lives(&start),
+            frame: atlas.frame("heart"),
             start: Vec2i(240, 56),
             spacing: 18,
         },
@@ -367,10 +135,41 @@ fn main() {
         choice_frame: 0,
         contacts: vec![],
         window: Vec2i(0, 0),
-        level: 0,
+        level: first_level,
+        first_level,
+        floor: 0,
         passed: false,
-        fonts: Fonts::new(fonts),
+        icons,
+        fight_message: String::new(),
+        dialogue: tutorial(&assets.fonts, &atlas),
+        after_dialogue: GameMode::Map,
+        assets,
+    };
+    // Headless mode for CI: render each screen offscreen and check it against the goldens
+    if let Some(dir) = golden_dir() {
+        if !check_goldens(&mut state, &animations, Path::new(&dir), updating_goldens()) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let event_loop = EventLoop::new();
+    let mut input = WinitInputHelper::new();
+    let window = {
+        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+        WindowBuilder::new()
+            .with_title("Title")
+            .with_inner_size(size)
+            .with_min_inner_size(size)
+            .with_resizable(false)
+            .build(&event_loop)
+            .unwrap()
     };
+    let mut pixels = {
+        let window_size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture).unwrap()
+    };
+
     // How many frames have we simulated
     let mut frame_count: usize = 0;
     // How many unsimulated frames have we saved up
@@ -385,7 +184,7 @@ fn main() {
             let mut screen = Screen::wrap(pixels.get_frame(), WIDTH, HEIGHT, DEPTH, state.window);
             screen.clear(Rgba(0, 0, 0, 0));
 
-            draw_game(&mut state, &mut screen, &levels, &animations, frame_count);
+            draw_game(&mut state, &mut screen, &animations, frame_count);
 
             // Flip buffers
             if pixels.render().is_err() {
@@ -409,12 +208,16 @@ fn main() {
                 pixels.resize(size.width, size.height);
             }
         }
+        // Pick up any art that changed on disk
+        if !state.assets.reload_changed().is_empty() {
+            atlas.refresh();
+        }
         // And the simulation "consumes" it
         while available_time >= DT {
             // Eat up one frame worth of time
             available_time -= DT;
 
-            update_game(&mut state, &input, frame_count, &levels);
+            update_game(&mut state, &input, frame_count);
 
             // Increment the frame counter
             frame_count += 1;
@@ -426,14 +229,65 @@ fn main() {
     });
 }
 
-fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, animations: &[Animation], frame: usize) {
+/// The directory passed with `--golden <dir>`, if any
+fn golden_dir() -> Option<String> {
+    std::env::args().skip_while(|a| a != "--golden").nth(1)
+}
+
+/// With --update-goldens the screens are saved as the new goldens instead of being checked
+fn updating_goldens() -> bool {
+    std::env::args().any(|a| a == "--update-goldens")
+}
+
+/**
+ * Draws each game mode into a headless canvas and compares it to the golden images,
+ * or saves them as the goldens if `update` is set
+ *
+ * Params:
+ *   - state: the freshly set up game state
+ *   - animations: the player animations
+ *   - dir: directory holding `<mode>.png` goldens
+ *   - update: write the goldens instead of checking them
+ *
+ * Returns whether every screen matched (or was saved)
+ **/
+fn check_goldens(state: &mut GameState, animations: &[Animation], dir: &Path, update: bool) -> bool {
+    let mut ok = true;
+    for (name, mode) in vec![
+        ("title", GameMode::Title),
+        ("map", GameMode::Map),
+        ("fight", GameMode::Fight),
+        ("fight_choice", GameMode::FightChoice),
+        ("game_over", GameMode::GameOver),
+        ("win", GameMode::Win),
+        ("dialogue", GameMode::Dialogue),
+    ] {
+        state.mode = mode;
+        let camera = state.window;
+        let mut canvas = Canvas::new(WIDTH, HEIGHT);
+        draw_game(state, &mut canvas.screen(camera), animations, 0);
+        let path = dir.join(format!("{}.png", name));
+        let result = if update {
+            update_golden(&canvas, &path)
+        } else {
+            check_golden(&canvas, &path, GOLDEN_TOLERANCE)
+        };
+        if let Err(e) = result {
+            eprintln!("{}: {}", name, e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+fn draw_game(state: &mut GameState, screen: &mut Screen, animations: &[Animation], frame: usize) {
+    let level = Rc::clone(state.assets.level(state.level));
     // Call screen's drawing methods to render the game state
     screen.clear(Rgba(80, 80, 80, 255));
 
     match state.mode {
         GameMode::Title => {
             // draws menu screen
-            // levels[state.level].0.draw(screen);
 
             let w = WIDTH as i32;
             let h = HEIGHT as i32;
@@ -447,48 +301,35 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
             screen.rect(menu_rect, Rgba(53, 40, 33, 255));
             screen.empty_rect(menu_rect, 4, Rgba(250, 30, 10, 255));
 
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 6) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("DUNGEONS\nand\nDINOS", 45.0, 0),
-            );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(250, 30, 10, 255),
-            );
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (2 * HEIGHT / 3) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("Press ENTER to start", 20.0, 0),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "DUNGEONS\nand\nDINOS",
+                Rect {
+                    x: w / 6,
+                    y: h / 6,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 6) as u16,
+                },
+                &TextFormat::new(45.0, Rgba(250, 30, 10, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(250, 30, 10, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "Press ENTER to start",
+                Rect {
+                    x: w / 6,
+                    y: 2 * h / 3,
+                    w: (2 * w / 3) as u16,
+                    h: (h - 2 * h / 3) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(250, 30, 10, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
         }
-        GameMode::Map => {
-            for m in levels[state.level].0.iter() {
-                m.draw(screen);
-            }
+        GameMode::Map | GameMode::Dialogue => {
+            level.map.draw_background(screen, frame);
             if !state.passed {
-                for (si, s) in levels[state.level].1.iter().enumerate() {
+                for (si, s) in level.sprites.iter().enumerate() {
                     screen.draw_sprite(&s);
                 }
             }
@@ -496,6 +337,22 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
             // TODO: With reversed bitblt, reverse left facing animations
             state.player.frame = animations[state.player.animation].current_frame(state.player.animation_start, frame);
             screen.draw_sprite(&state.player);
+            level.map.draw_foreground(screen, frame);
+
+            if let GameMode::Dialogue = state.mode {
+                // The dialogue box stays put on screen while the map scrolls
+                screen.push_viewport(Viewport::new(
+                    Rect {
+                        x: 0,
+                        y: 0,
+                        w: WIDTH as u16,
+                        h: HEIGHT as u16,
+                    },
+                    Vec2i(0, 0),
+                ));
+                screen.draw_dialogue(&mut state.assets.fonts, &state.dialogue);
+                screen.pop_viewport();
+            }
         }
         GameMode::Fight => {
             state.window = Vec2i(0, 0);
@@ -528,28 +385,11 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                 Vec2i(32, 32),
                 false,
             );
-            screen.bitblt(
-                &levels[state.level].1[0].image,
-                levels[state.level].1[0].frame,
-                Vec2i(264, 32),
-                true,
-            );
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+            if let Some((_, enemy)) = level.enemy() {
+                screen.bitblt(&enemy.image, enemy.frame, Vec2i(264, 32), true);
+            }
             match state.player_choice {
                 Attack::Aggressive => {
-                    layout.reset(&LayoutSettings {
-                        x: 32.0,
-                        y: 85.0,
-                        max_height: Some((h / 6) as f32),
-                        vertical_align: fontdue::layout::VerticalAlign::Middle,
-                        max_width: Some(((w) / 3) as f32),
-                        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                        ..LayoutSettings::default()
-                    });
-                    layout.append(
-                        &state.fonts.font_list,
-                        &TextStyle::new("aggressive", 20.0, 0),
-                    );
                     screen.rect(
                         Rect {
                             x: 32,
@@ -569,21 +409,20 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                         2,
                         Rgba(110, 45, 15, 255),
                     );
+                    screen.draw_text_box(
+                        &mut state.assets.fonts,
+                        "aggressive",
+                        Rect {
+                            x: 32,
+                            y: 85,
+                            w: (w / 3) as u16,
+                            h: (h / 6) as u16,
+                        },
+                        &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                            .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
+                    );
                 }
                 Attack::Defensive => {
-                    layout.reset(&LayoutSettings {
-                        x: 32.0,
-                        y: 135.0,
-                        max_height: Some((h / 6) as f32),
-                        vertical_align: fontdue::layout::VerticalAlign::Middle,
-                        max_width: Some(((w) / 3) as f32),
-                        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                        ..LayoutSettings::default()
-                    });
-                    layout.append(
-                        &state.fonts.font_list,
-                        &TextStyle::new("defensive", 20.0, 0),
-                    );
                     screen.rect(
                         Rect {
                             x: 32,
@@ -603,18 +442,20 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                         2,
                         Rgba(110, 45, 15, 255),
                     );
+                    screen.draw_text_box(
+                        &mut state.assets.fonts,
+                        "defensive",
+                        Rect {
+                            x: 32,
+                            y: 135,
+                            w: (w / 3) as u16,
+                            h: (h / 6) as u16,
+                        },
+                        &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                            .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
+                    );
                 }
                 Attack::Sneaky => {
-                    layout.reset(&LayoutSettings {
-                        x: 32.0,
-                        y: 185.0,
-                        max_height: Some((h / 6) as f32),
-                        vertical_align: fontdue::layout::VerticalAlign::Middle,
-                        max_width: Some(((w) / 3) as f32),
-                        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                        ..LayoutSettings::default()
-                    });
-                    layout.append(&state.fonts.font_list, &TextStyle::new("sneaky", 20.0, 0));
                     screen.rect(
                         Rect {
                             x: 32,
@@ -634,30 +475,23 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                         2,
                         Rgba(110, 45, 15, 255),
                     );
+                    screen.draw_text_box(
+                        &mut state.assets.fonts,
+                        "sneaky",
+                        Rect {
+                            x: 32,
+                            y: 185,
+                            w: (w / 3) as u16,
+                            h: (h / 6) as u16,
+                        },
+                        &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                            .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
+                    );
                 }
                 _ => {}
             }
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
-            );
             match state.enemy_choice {
                 Attack::Aggressive => {
-                    layout.reset(&LayoutSettings {
-                        x: 182.0,
-                        y: 85.0,
-                        max_height: Some((h / 6) as f32),
-                        vertical_align: fontdue::layout::VerticalAlign::Middle,
-                        max_width: Some(((w) / 3) as f32),
-                        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                        ..LayoutSettings::default()
-                    });
-                    layout.append(
-                        &state.fonts.font_list,
-                        &TextStyle::new("aggressive", 20.0, 0),
-                    );
                     screen.rect(
                         Rect {
                             x: 182,
@@ -677,21 +511,20 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                         2,
                         Rgba(110, 45, 15, 255),
                     );
+                    screen.draw_text_box(
+                        &mut state.assets.fonts,
+                        "aggressive",
+                        Rect {
+                            x: 182,
+                            y: 85,
+                            w: (w / 3) as u16,
+                            h: (h / 6) as u16,
+                        },
+                        &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                            .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
+                    );
                 }
                 Attack::Defensive => {
-                    layout.reset(&LayoutSettings {
-                        x: 182.0,
-                        y: 135.0,
-                        max_height: Some((h / 6) as f32),
-                        vertical_align: fontdue::layout::VerticalAlign::Middle,
-                        max_width: Some(((w) / 3) as f32),
-                        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                        ..LayoutSettings::default()
-                    });
-                    layout.append(
-                        &state.fonts.font_list,
-                        &TextStyle::new("defensive", 20.0, 0),
-                    );
                     screen.rect(
                         Rect {
                             x: 182,
@@ -711,18 +544,20 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                         2,
                         Rgba(110, 45, 15, 255),
                     );
+                    screen.draw_text_box(
+                        &mut state.assets.fonts,
+                        "defensive",
+                        Rect {
+                            x: 182,
+                            y: 135,
+                            w: (w / 3) as u16,
+                            h: (h / 6) as u16,
+                        },
+                        &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                            .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
+                    );
                 }
                 Attack::Sneaky => {
-                    layout.reset(&LayoutSettings {
-                        x: 182.0,
-                        y: 185.0,
-                        max_height: Some((h / 6) as f32),
-                        vertical_align: fontdue::layout::VerticalAlign::Middle,
-                        max_width: Some(((w) / 3) as f32),
-                        horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                        ..LayoutSettings::default()
-                    });
-                    layout.append(&state.fonts.font_list, &TextStyle::new("sneaky", 20.0, 0));
                     screen.rect(
                         Rect {
                             x: 182,
@@ -742,14 +577,33 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                         2,
                         Rgba(110, 45, 15, 255),
                     );
+                    screen.draw_text_box(
+                        &mut state.assets.fonts,
+                        "sneaky",
+                        Rect {
+                            x: 182,
+                            y: 185,
+                            w: (w / 3) as u16,
+                            h: (h / 6) as u16,
+                        },
+                        &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                            .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
+                    );
                 }
                 _ => {}
             }
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
+            screen.draw_rich_text(
+                &mut state.assets.fonts,
+                &state.icons,
+                &state.fight_message,
+                Rect {
+                    x: 10,
+                    y: 228,
+                    w: (WIDTH - 20) as u16,
+                    h: 18,
+                },
+                &TextFormat::new(16.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
         }
         GameMode::FightChoice => {
@@ -784,12 +638,9 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                 Vec2i(32, 32),
                 false,
             );
-            screen.bitblt(
-                &levels[state.level].1[0].image,
-                levels[state.level].1[0].frame,
-                Vec2i(264, 32),
-                true,
-            );
+            if let Some((_, enemy)) = level.enemy() {
+                screen.bitblt(&enemy.image, enemy.frame, Vec2i(264, 32), true);
+            }
             screen.rect(
                 Rect {
                     x: 32,
@@ -910,108 +761,77 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
                 Rgba(110, 45, 15, 255),
             );
 
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-            layout.reset(&LayoutSettings {
-                x: 32.0,
-                y: 85.0,
-                max_height: Some((h / 6) as f32),
-                vertical_align: fontdue::layout::VerticalAlign::Middle,
-                max_width: Some(((w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("[a]ggressive", 20.0, 0),
-            );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
-            );
-            layout.reset(&LayoutSettings {
-                x: 32.0,
-                y: 135.0,
-                max_height: Some((h / 6) as f32),
-                vertical_align: fontdue::layout::VerticalAlign::Middle,
-                max_width: Some(((w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("[d]efensive", 20.0, 0),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "[a]ggressive",
+                Rect {
+                    x: 32,
+                    y: 85,
+                    w: (w / 3) as u16,
+                    h: (h / 6) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "[d]efensive",
+                Rect {
+                    x: 32,
+                    y: 135,
+                    w: (w / 3) as u16,
+                    h: (h / 6) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
-            layout.reset(&LayoutSettings {
-                x: 32.0,
-                y: 185.0,
-                max_height: Some((h / 6) as f32),
-                vertical_align: fontdue::layout::VerticalAlign::Middle,
-                max_width: Some(((w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(&state.fonts.font_list, &TextStyle::new("[s]neaky", 20.0, 0));
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "[s]neaky",
+                Rect {
+                    x: 32,
+                    y: 185,
+                    w: (w / 3) as u16,
+                    h: (h / 6) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
-            layout.reset(&LayoutSettings {
-                x: 182.0,
-                y: 85.0,
-                max_height: Some((h / 6) as f32),
-                vertical_align: fontdue::layout::VerticalAlign::Middle,
-                max_width: Some(((w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(&state.fonts.font_list, &TextStyle::new("...", 20.0, 0));
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "...",
+                Rect {
+                    x: 182,
+                    y: 85,
+                    w: (w / 3) as u16,
+                    h: (h / 6) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
-            layout.reset(&LayoutSettings {
-                x: 182.0,
-                y: 135.0,
-                max_height: Some((h / 6) as f32),
-                vertical_align: fontdue::layout::VerticalAlign::Middle,
-                max_width: Some(((w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(&state.fonts.font_list, &TextStyle::new("...", 20.0, 0));
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "...",
+                Rect {
+                    x: 182,
+                    y: 135,
+                    w: (w / 3) as u16,
+                    h: (h / 6) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
-            layout.reset(&LayoutSettings {
-                x: 182.0,
-                y: 185.0,
-                max_height: Some((h / 6) as f32),
-                vertical_align: fontdue::layout::VerticalAlign::Middle,
-                max_width: Some(((w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(&state.fonts.font_list, &TextStyle::new("...", 20.0, 0));
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(110, 50, 20, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "...",
+                Rect {
+                    x: 182,
+                    y: 185,
+                    w: (w / 3) as u16,
+                    h: (h / 6) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(110, 50, 20, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Middle),
             );
         }
         GameMode::GameOver => {
@@ -1029,40 +849,29 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
             screen.rect(menu_rect, Rgba(53, 40, 33, 255));
             screen.empty_rect(menu_rect, 4, Rgba(250, 30, 10, 255));
 
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 6) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("GAME\nOVER", 45.0, 0),
-            );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(250, 30, 10, 255),
-            );
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 2) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("Press ENTER to play again", 20.0, 0),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "GAME\nOVER",
+                Rect {
+                    x: w / 6,
+                    y: h / 6,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 6) as u16,
+                },
+                &TextFormat::new(45.0, Rgba(250, 30, 10, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(250, 30, 10, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "Press ENTER to play again",
+                Rect {
+                    x: w / 6,
+                    y: h / 2,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 2) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(250, 30, 10, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
         }
         GameMode::Win => {
@@ -1079,47 +888,51 @@ fn draw_game(state: &mut GameState, screen: &mut Screen, levels: &Vec<Level>, an
             screen.rect(menu_rect, Rgba(53, 40, 33, 255));
             screen.empty_rect(menu_rect, 4, Rgba(250, 30, 10, 255));
 
-            let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 6) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(&state.fonts.font_list, &TextStyle::new("YOU\nWIN", 45.0, 0));
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(250, 30, 10, 255),
-            );
-            layout.reset(&LayoutSettings {
-                x: (WIDTH / 6) as f32,
-                y: (HEIGHT / 2) as f32,
-                max_width: Some(((2 * w) / 3) as f32),
-                horizontal_align: fontdue::layout::HorizontalAlign::Center,
-                ..LayoutSettings::default()
-            });
-            layout.append(
-                &state.fonts.font_list,
-                &TextStyle::new("Press ENTER to play again", 20.0, 0),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "YOU\nWIN",
+                Rect {
+                    x: w / 6,
+                    y: h / 6,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 6) as u16,
+                },
+                &TextFormat::new(45.0, Rgba(250, 30, 10, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
-            screen.draw_text(
-                &mut state.fonts.rasterized,
-                &state.fonts.font_list[0],
-                &mut layout,
-                Rgba(250, 30, 10, 255),
+            screen.draw_text_box(
+                &mut state.assets.fonts,
+                "Press ENTER to play again",
+                Rect {
+                    x: w / 6,
+                    y: h / 2,
+                    w: (2 * w / 3) as u16,
+                    h: (h - h / 2) as u16,
+                },
+                &TextFormat::new(20.0, Rgba(250, 30, 10, 255))
+                    .with_align(HorizontalAlign::Center, VerticalAlign::Top),
             );
         }
     }
 }
 
-fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, levels: &Vec<Level>) {
+fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize) {
+    let level = Rc::clone(state.assets.level(state.level));
     match state.mode {
         GameMode::Title => {
             if input.key_held(VirtualKeyCode::Return) {
-                state.mode = GameMode::Map;
+                state.dialogue.restart();
+                state.after_dialogue = GameMode::Map;
+                state.mode = GameMode::Dialogue;
+            }
+        }
+        GameMode::Dialogue => {
+            state.dialogue.update();
+            if input.key_pressed(VirtualKeyCode::Space) || input.key_pressed(VirtualKeyCode::Return) {
+                state.dialogue.advance();
+            }
+            if state.dialogue.finished() {
+                state.mode = std::mem::replace(&mut state.after_dialogue, GameMode::Map);
             }
         }
         GameMode::Map => {
@@ -1178,24 +991,29 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
             state.contacts.clear();
             let mut statics = &vec![];
             if !state.passed {
-                statics = &levels[state.level].1;
+                statics = &level.sprites;
             }
-            gather_contacts(
-                &levels[state.level].0[0],
+            gather_map_contacts(
+                &level.map,
                 &state.player,
                 statics,
                 &mut state.contacts,
             );
 
-            match restitute(
-                &levels[state.level].0[0],
+            match restitute_map(
+                &level.map,
                 &mut state.player,
                 statics,
                 &mut state.contacts,
             ) {
-                Effect::Fight => {
-                    state.mode = GameMode::FightChoice;
-                    
+                Effect::Fight => match level.enemy() {
+                    Some((_, enemy)) => {
+                        state.dialogue = taunt(&state.assets.fonts, enemy, state.floor);
+                        state.after_dialogue = GameMode::FightChoice;
+                        state.mode = GameMode::Dialogue;
+                    }
+                    // Something without an enemy to speak for it, like a tile, just starts the fight
+                    None => state.mode = GameMode::FightChoice,
                 },
                 Effect::Win => { 
                     state.mode = GameMode::Win;
@@ -1203,34 +1021,31 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
                 _ => {}
             }
 
-            if state.player.position.0 < 192
-                && state.player.position.0 > 150
-                && state.player.position.1 < 8
-            {
-                state.level += 1;
-                state.player.position = Vec2i(128, 352);
-                state.window = Vec2i(0, 128);
-                state.passed = false;
+            if let Some(exit) = level.exit_at(state.player.position) {
+                match state.assets.find_level(&exit.to) {
+                    Some(next) => {
+                        enter_level(state, next);
+                        state.floor += 1;
+                        return;
+                    }
+                    None => eprintln!("No level called {}", exit.to),
+                }
             }
 
             if state.player.position.1 > (state.window.1 + HEIGHT as i32 - 32) {
                 state.window.1 += 2;
-                if state.window.1 > HEIGHT as i32 * 2 {
-                    state.window.1 = HEIGHT as i32 * 2;
-                }
             }
             if state.player.position.1 < (state.window.1 + 32) {
                 state.window.1 -= 2;
-                if state.window.1 < 0 {
-                    state.window.1 = 0;
-                }
             }
+            state.window = level.clamp_camera(state.window, (WIDTH, HEIGHT));
         }
         GameMode::FightChoice => {
+            let lives = (state.health.lives, state.enemy_health.lives);
             if input.key_held(VirtualKeyCode::A) {
                 let enemy_choice = get_enemy_decision(
                     state,
-                    levels[state.level].2,
+                    enemy_lives(&level),
                 );
                 let mut rng = rand::thread_rng();
                 state.enemy_choice = enemy_choice;
@@ -1239,7 +1054,7 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
                     Attack::Aggressive => {}
                     Attack::Defensive => {
                         let decision = rng.gen_bool(0.5);
-                        if decision && state.enemy_health.lives < levels[state.level].2 {
+                        if decision && state.enemy_health.lives < enemy_lives(&level) {
                             state.enemy_health.lives += 1;
                         }
                         else{
@@ -1262,7 +1077,7 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
             else if input.key_held(VirtualKeyCode::S) {
                 let enemy_choice = get_enemy_decision(
                     state,
-                    levels[state.level].2,
+                    enemy_lives(&level),
                 );
                 let mut rng = rand::thread_rng();
                 state.enemy_choice = enemy_choice;
@@ -1294,7 +1109,7 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
             else if input.key_held(VirtualKeyCode::D) {
                 let enemy_choice = get_enemy_decision(
                     state,
-                    levels[state.level].2,
+                    enemy_lives(&level),
                 );
                 let mut rng = rand::thread_rng();
                 state.enemy_choice = enemy_choice;
@@ -1324,11 +1139,18 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
                 state.mode = GameMode::Fight;
                 state.choice_frame = frame;
             }
+            if let GameMode::Fight = state.mode {
+                state.fight_message = fight_message(
+                    state.player_choice,
+                    state.enemy_choice,
+                    lives.0 as i32 - state.health.lives as i32,
+                    lives.1 as i32 - state.enemy_health.lives as i32,
+                );
+            }
         }
         GameMode::Fight => {
             if frame - state.choice_frame > 120 {
                 if state.enemy_health.lives == 0 {
-                    state.enemy_health.lives = levels[state.level+1].2;
                     state.health.lives = 5;
                     state.mode = GameMode::Map;
                     state.passed = true;
@@ -1342,13 +1164,13 @@ fn update_game(state: &mut GameState, input: &WinitInputHelper, frame: usize, le
         GameMode::GameOver => {
             if input.key_held(VirtualKeyCode::Return) {
                 state.mode = GameMode::Map;
-                reset_game(state, levels);
+                reset_game(state);
             }
         }
         GameMode::Win => {
             if input.key_held(VirtualKeyCode::Return) {
                 state.mode = GameMode::Map;
-                reset_game(state, levels);
+                reset_game(state);
             }
         }
     }
@@ -1389,17 +1211,100 @@ fn get_enemy_decision(state: &mut GameState, enemy_cap: usize) -> Attack {
     }
 }
 
+/// Where dialogue boxes go on screen
+const DIALOGUE_RECT: Rect = Rect {
+    x: 8,
+    y: HEIGHT as i32 - 80,
+    w: WIDTH as u16 - 16,
+    h: 72,
+};
+
+/// The rules, told by the player's dino before the first level
+fn tutorial(fonts: &Fonts, atlas: &Atlas) -> Dialogue {
+    let portrait = Portrait::new(atlas.texture(), atlas.frame("dino/0"));
+    let say = |text: &str| Message::new(text).with_speaker("You", Some(portrait.clone()));
+    Dialogue::new(
+        fonts,
+        vec![
+            say("Another dungeon full of dinos. Use the arrow keys to look around and find the stairs up."),
+            say("If a dino blocks the way I'll have to fight it. Each round I can be [a]ggressive, [d]efensive or [s]neaky."),
+            say("Aggressive beats sneaky, sneaky beats defensive and defensive beats aggressive. Press SPACE to go!"),
+        ],
+        DIALOGUE_RECT,
+        TextFormat::new(16.0, Rgba(245, 240, 215, 255)),
+    )
+}
+
+const TAUNTS: [&str; 3] = [
+    "You'll never get past me, little dino!",
+    "Back for more? I'm a lot tougher than the last one.",
+    "Nobody touches the gem. Nobody!",
+];
+
+/// What the enemy says before a fight on this level
+fn taunt(fonts: &Fonts, enemy: &Sprite, level: usize) -> Dialogue {
+    Dialogue::new(
+        fonts,
+        vec![Message::new(TAUNTS[level % TAUNTS.len()])
+            .with_speaker("Enemy", Some(Portrait::new(&enemy.image, enemy.frame)))],
+        DIALOGUE_RECT,
+        TextFormat::new(16.0, Rgba(245, 240, 215, 255)),
+    )
+}
+
+fn attack_markup(attack: Attack) -> &'static str {
+    match attack {
+        Attack::Aggressive => "[color=#c03020]aggressive[/color]",
+        Attack::Defensive => "[color=#2050c0]defensive[/color]",
+        Attack::Sneaky => "[color=#308030]sneaky[/color]",
+        Attack::Nothing => "nothing",
+    }
+}
+
+/// Describe a round of the fight, e.g. "aggressive beats sneaky: enemy -2 <heart>"
+fn fight_message(player: Attack, enemy: Attack, player_lost: i32, enemy_lost: i32) -> String {
+    let verb = match (player, enemy) {
+        (a, b) if a == b => "ties",
+        (Attack::Aggressive, Attack::Sneaky)
+        | (Attack::Sneaky, Attack::Defensive)
+        | (Attack::Defensive, Attack::Aggressive) => "beats",
+        _ => "loses to",
+    };
+    let mut message = format!("{} {} {}", attack_markup(player), verb, attack_markup(enemy));
+    for (who, lost) in [("you", player_lost), ("enemy", enemy_lost)].iter() {
+        if *lost != 0 {
+            message += &format!(" {} {:+} [icon=heart]", who, -lost);
+        }
+    }
+    message
+}
 
-fn reset_game(state: &mut GameState, levels: &[Level]) {
-    state.player.position = Vec2i(136, 224);
+/// How many lives a level's enemy starts with, 0 if there isn't one
+fn enemy_lives(level: &Level) -> usize {
+    level.enemy().map_or(0, |(enemy, _)| enemy.health)
+}
+
+/// Put the player at a level's start, with the camera scrolled to show them
+fn enter_level(state: &mut GameState, handle: Handle<Level>) {
+    let level = Rc::clone(state.assets.level(handle));
+    state.level = handle;
+    state.player.position = level.data.player_spawn;
+    state.window = level.clamp_camera(
+        Vec2i(0, level.data.player_spawn.1 + 32 - HEIGHT as i32),
+        (WIDTH, HEIGHT),
+    );
+    state.enemy_health.lives = enemy_lives(&level);
+    state.passed = false;
+}
+
+fn reset_game(state: &mut GameState) {
+    enter_level(state, state.first_level);
+    state.floor = 0;
     state.health.lives = 5;
-    state.enemy_health.lives = levels[0].2;
     state.player_choice = Attack::Nothing;
     state.enemy_choice = Attack::Nothing;
     state.choice_frame = 0;
+    state.fight_message.clear();
     state.contacts.clear();
-    state.window = Vec2i(0, 0);
-    state.level = 0;
-    state.passed = false;
     state.thresholds = vec![33,33,33]
 }
@@ -0,0 +1,278 @@
+use crate::screen::{Blend, Screen};
+use crate::texture::{Texture, TextureError};
+use crate::types::{Rect, Rgba, Vec2i};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Where one character is on the font's texture and how to place it
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct BitmapGlyph {
+    pub frame: Rect,
+    /// Where the frame's top left goes relative to the pen position
+    pub offset: Vec2i,
+    /// How far to move the pen after drawing
+    pub advance: i32,
+}
+
+/// A font drawn from a sprite sheet with bitblt instead of rasterized at runtime.
+/// Good for pixel art text, since it's always drawn 1:1.
+pub struct BitmapFont {
+    image: Rc<Texture>,
+    glyphs: HashMap<char, BitmapGlyph>,
+    kerning: HashMap<(char, char), i32>,
+    /// Distance from one line's top to the next line's top
+    pub line_height: i32,
+    /// Distance from a line's top to its baseline
+    pub base: i32,
+    /// Drawn in place of characters the font doesn't have
+    pub fallback: Option<char>,
+}
+
+impl BitmapFont {
+    /**
+     * Makes a font from a texture cut into equal cells
+     *
+     * Params:
+     *   - image: the sprite sheet
+     *   - cell_w, cell_h: the size of each character's cell
+     *   - chars: the characters in the sheet, left to right then top to bottom
+     **/
+    pub fn grid(image: &Rc<Texture>, cell_w: u16, cell_h: u16, chars: &str) -> Self {
+        let columns = (image.size().0 / cell_w as usize).max(1);
+        let glyphs = chars
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let frame = Rect {
+                    x: (i % columns) as i32 * cell_w as i32,
+                    y: (i / columns) as i32 * cell_h as i32,
+                    w: cell_w,
+                    h: cell_h,
+                };
+                assert!(image.valid_frame(frame), "Font sheet is too small for {:?}", c);
+                let glyph = BitmapGlyph {
+                    frame,
+                    offset: Vec2i(0, 0),
+                    advance: cell_w as i32,
+                };
+                (c, glyph)
+            })
+            .collect();
+        Self {
+            image: Rc::clone(image),
+            glyphs,
+            kerning: HashMap::new(),
+            line_height: cell_h as i32,
+            base: cell_h as i32,
+            fallback: None,
+        }
+    }
+    /**
+     * Loads a font in the BMFont text format (a .fnt file next to its PNG)
+     *
+     * Params:
+     *   - path: the .fnt file
+     *
+     * Note: Only single page fonts are supported. The page's image is loaded
+     * relative to the .fnt file.
+     **/
+    pub fn from_bmfont(path: &Path) -> Result<Self, BitmapFontError> {
+        let text = read_to_string(path).map_err(|e| BitmapFontError::Io(path.to_path_buf(), e))?;
+        let parse_error = |msg: String| BitmapFontError::Parse(path.to_path_buf(), msg);
+        let mut line_height = None;
+        let mut base = None;
+        let mut page = None;
+        let mut glyphs = HashMap::new();
+        let mut kerning = HashMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let mut words = line.splitn(2, char::is_whitespace);
+            let tag = words.next().unwrap_or("");
+            let attrs = parse_attributes(words.next().unwrap_or(""));
+            let int = |key: &str| -> Result<i32, BitmapFontError> {
+                attrs
+                    .get(key)
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| parse_error(format!("line {}: missing or bad {}", n + 1, key)))
+            };
+            let ch = |key: &str| -> Result<char, BitmapFontError> {
+                std::char::from_u32(int(key)? as u32)
+                    .ok_or_else(|| parse_error(format!("line {}: {} isn't a character", n + 1, key)))
+            };
+            match tag {
+                "common" => {
+                    line_height = Some(int("lineHeight")?);
+                    base = Some(int("base")?);
+                    if int("pages").unwrap_or(1) > 1 {
+                        return Err(parse_error("only single page fonts are supported".to_string()));
+                    }
+                }
+                "page" => {
+                    let file = attrs
+                        .get("file")
+                        .ok_or_else(|| parse_error(format!("line {}: page has no file", n + 1)))?;
+                    page = Some(path.with_file_name(file));
+                }
+                "char" => {
+                    let glyph = BitmapGlyph {
+                        frame: Rect {
+                            x: int("x")?,
+                            y: int("y")?,
+                            w: int("width")? as u16,
+                            h: int("height")? as u16,
+                        },
+                        offset: Vec2i(int("xoffset")?, int("yoffset")?),
+                        advance: int("xadvance")?,
+                    };
+                    glyphs.insert(ch("id")?, glyph);
+                }
+                "kerning" => {
+                    kerning.insert((ch("first")?, ch("second")?), int("amount")?);
+                }
+                _ => {}
+            }
+        }
+        let page = page.ok_or_else(|| parse_error("no page image".to_string()))?;
+        let image = Rc::new(Texture::load(&page)?);
+        for (c, glyph) in glyphs.iter() {
+            if !image.valid_frame(glyph.frame) {
+                return Err(parse_error(format!("glyph {:?} is outside {}", c, page.display())));
+            }
+        }
+        Ok(Self {
+            image,
+            glyphs,
+            kerning,
+            line_height: line_height.ok_or_else(|| parse_error("no common line".to_string()))?,
+            base: base.unwrap_or(0),
+            fallback: None,
+        })
+    }
+    pub fn with_fallback(self, fallback: char) -> Self {
+        Self {
+            fallback: Some(fallback),
+            ..self
+        }
+    }
+    pub fn image(&self) -> &Rc<Texture> {
+        &self.image
+    }
+    /// The glyph for a character, or the fallback's glyph if there isn't one
+    pub fn glyph(&self, c: char) -> Option<&BitmapGlyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.fallback.and_then(|f| self.glyphs.get(&f)))
+    }
+    /// Extra space between a pair of characters
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0)
+    }
+    /// How far a single line of text advances
+    pub fn text_width(&self, text: &str) -> i32 {
+        let mut width = 0;
+        let mut prev = None;
+        for c in text.chars() {
+            if let Some(p) = prev {
+                width += self.kerning(p, c);
+            }
+            width += self.glyph(c).map_or(0, |g| g.advance);
+            prev = Some(c);
+        }
+        width
+    }
+    /// Width and height of text, which may have line breaks
+    pub fn measure(&self, text: &str) -> (i32, i32) {
+        let lines = text.split('\n');
+        let height = lines.clone().count() as i32 * self.line_height;
+        (lines.map(|l| self.text_width(l)).max().unwrap_or(0), height)
+    }
+}
+
+/// Split `key=value key="quoted value"` pairs into a map
+fn parse_attributes(text: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = text.trim_start();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim().to_string();
+        rest = &rest[(eq + 1)..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let value = &quoted[..end];
+            rest = &quoted[(end + 1).min(quoted.len())..];
+            value
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+        attrs.insert(key, value.to_string());
+        rest = rest.trim_start();
+    }
+    attrs
+}
+
+#[derive(Debug)]
+pub enum BitmapFontError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, String),
+    /// The page image couldn't be loaded
+    Texture(TextureError),
+}
+impl fmt::Display for BitmapFontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitmapFontError::Io(path, e) => write!(f, "couldn't read font {}: {}", path.display(), e),
+            BitmapFontError::Parse(path, e) => {
+                write!(f, "couldn't parse font {}: {}", path.display(), e)
+            }
+            BitmapFontError::Texture(e) => write!(f, "couldn't load font page: {}", e),
+        }
+    }
+}
+impl std::error::Error for BitmapFontError {}
+impl From<TextureError> for BitmapFontError {
+    fn from(e: TextureError) -> Self {
+        BitmapFontError::Texture(e)
+    }
+}
+
+pub trait DrawBitmapTextExt {
+    fn draw_bitmap_text(&mut self, font: &BitmapFont, text: &str, pos: Vec2i, col: Rgba);
+}
+
+impl<'fb> DrawBitmapTextExt for Screen<'fb> {
+    /**
+     * Draws text with a bitmap font
+     *
+     * Params:
+     *   - font: the font to use
+     *   - text: what to write, which may have line breaks
+     *   - pos: the top left of the first line, in world coordinates
+     *   - col: tints the glyphs; white leaves them as they are in the sheet
+     **/
+    fn draw_bitmap_text(&mut self, font: &BitmapFont, text: &str, pos: Vec2i, col: Rgba) {
+        let blend = Blend::default().with_tint(col);
+        for (row, line) in text.split('\n').enumerate() {
+            let y = pos.1 + row as i32 * font.line_height;
+            let mut x = pos.0;
+            let mut prev = None;
+            for c in line.chars() {
+                if let Some(p) = prev {
+                    x += font.kerning(p, c);
+                }
+                prev = Some(c);
+                if let Some(glyph) = font.glyph(c) {
+                    if glyph.frame.w > 0 && glyph.frame.h > 0 {
+                        let to = Vec2i(x + glyph.offset.0, y + glyph.offset.1);
+                        self.bitblt_blend(&font.image, glyph.frame, to, false, blend);
+                    }
+                    x += glyph.advance;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::screen::Screen;
+use crate::types::{Rgba, Vec2i};
+use image::{self, ImageError, RgbaImage};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// An owned framebuffer that doesn't need a window.
+/// Borrow a `Screen` from it to draw with all the usual drawing code,
+/// then save it out or compare it against a golden image.
+pub struct Canvas {
+    buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+    depth: usize,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            buffer: vec![0; width * height * 4],
+            width,
+            height,
+            depth: 4,
+        }
+    }
+    /// Load a canvas from a PNG, e.g. a golden image saved with `save_png`.
+    /// The bytes are taken as-is, since they're exactly what was on screen.
+    pub fn from_png(path: &Path) -> Result<Self, ImageError> {
+        let image = image::open(path)?.into_rgba8();
+        let (width, height) = image.dimensions();
+        Ok(Self {
+            buffer: image.into_vec(),
+            width: width as usize,
+            height: height as usize,
+            depth: 4,
+        })
+    }
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+    /// Wrap the canvas in a `Screen` with the given camera position
+    pub fn screen(&mut self, position: Vec2i) -> Screen<'_> {
+        Screen::wrap(
+            &mut self.buffer,
+            self.width,
+            self.height,
+            self.depth,
+            position,
+        )
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> Rgba {
+        let idx = y * self.width * self.depth + x * self.depth;
+        let p = &self.buffer[idx..(idx + self.depth)];
+        Rgba(p[0], p[1], p[2], p[3])
+    }
+    pub fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_raw(self.width as u32, self.height as u32, self.buffer.clone())
+            .expect("Canvas buffer is the wrong size")
+    }
+    pub fn save_png(&self, path: &Path) -> Result<(), ImageError> {
+        self.to_image().save(path)
+    }
+    /// Compare against another canvas of the same size.
+    /// A pixel only counts as different if some channel is off by more than `tolerance`.
+    pub fn compare(&self, other: &Canvas, tolerance: u8) -> ImageDiff {
+        assert_eq!(self.size(), other.size(), "Can't compare canvases of different sizes");
+        let mut diff = ImageDiff {
+            differing: 0,
+            max_delta: 0,
+            first: None,
+        };
+        for (i, (a, b)) in self
+            .buffer
+            .chunks_exact(self.depth)
+            .zip(other.buffer.chunks_exact(other.depth))
+            .enumerate()
+        {
+            let delta = a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| (*a as i16 - *b as i16).abs() as u8)
+                .max()
+                .unwrap_or(0);
+            diff.max_delta = diff.max_delta.max(delta);
+            if delta > tolerance {
+                diff.differing += 1;
+                if diff.first.is_none() {
+                    diff.first = Some(Vec2i(
+                        (i % self.width) as i32,
+                        (i / self.width) as i32,
+                    ));
+                }
+            }
+        }
+        diff
+    }
+}
+
+/// The result of comparing two canvases
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ImageDiff {
+    /// How many pixels were outside the tolerance
+    pub differing: usize,
+    /// The biggest difference in any channel of any pixel
+    pub max_delta: u8,
+    /// The first differing pixel in row-major order
+    pub first: Option<Vec2i>,
+}
+impl ImageDiff {
+    pub fn matches(&self) -> bool {
+        self.differing == 0
+    }
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Image(ImageError),
+    /// There's no golden image to check against; make one with `update_golden`
+    Missing(PathBuf),
+    SizeMismatch {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    Mismatch(ImageDiff),
+}
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Image(e) => write!(f, "couldn't read or write golden image: {}", e),
+            GoldenError::Missing(path) => write!(f, "there's no golden image {}", path.display()),
+            GoldenError::SizeMismatch { expected, actual } => write!(
+                f,
+                "golden image is {}x{} but the canvas is {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            GoldenError::Mismatch(diff) => write!(
+                f,
+                "{} pixels differ from the golden image (max delta {}, first at {:?})",
+                diff.differing, diff.max_delta, diff.first
+            ),
+        }
+    }
+}
+impl std::error::Error for GoldenError {}
+impl From<ImageError> for GoldenError {
+    fn from(e: ImageError) -> Self {
+        GoldenError::Image(e)
+    }
+}
+
+/**
+ * Checks a canvas against the golden image at `path`
+ *
+ * Params:
+ *   - canvas: the rendered frame
+ *   - path: the golden PNG
+ *   - tolerance: how far each channel may be off before a pixel counts as different
+ *
+ * Note: A missing golden image is an error, so checks can't pass by accident.
+ * On a mismatch the canvas is saved next to the golden as `<name>.actual.png`.
+ **/
+pub fn check_golden(canvas: &Canvas, path: &Path, tolerance: u8) -> Result<(), GoldenError> {
+    if !path.exists() {
+        return Err(GoldenError::Missing(path.to_path_buf()));
+    }
+    let golden = Canvas::from_png(path)?;
+    if golden.size() != canvas.size() {
+        return Err(GoldenError::SizeMismatch {
+            expected: golden.size(),
+            actual: canvas.size(),
+        });
+    }
+    let diff = canvas.compare(&golden, tolerance);
+    if diff.matches() {
+        Ok(())
+    } else {
+        canvas.save_png(&path.with_extension("actual.png"))?;
+        Err(GoldenError::Mismatch(diff))
+    }
+}
+
+/// Save a canvas as the golden image at `path`, replacing any that's there
+pub fn update_golden(canvas: &Canvas, path: &Path) -> Result<(), GoldenError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(ImageError::from)?;
+    }
+    canvas.save_png(path)?;
+    Ok(())
+}
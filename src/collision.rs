@@ -5,9 +5,11 @@ use std::rc::Rc;
 use crate::sprite::Sprite;
 use crate::types::*;
 use crate::{sprite, tiles::*};
+use crate::layers::LayeredMap;
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 enum ColliderID {
+    /// (index of the sprite or tile layer, where it was hit, whether it's a sprite)
     Static((usize, Vec2i, bool)),
     Dynamic(usize),
 }
@@ -27,6 +29,24 @@ pub fn gather_contacts(
     statics: &[Sprite],
     into: &mut Vec<Contact>,
 ) {
+    gather_sprite_contacts(sprite, statics, into);
+    gather_tile_contacts(0, tilemap, sprite, into);
+}
+
+/// Like gather_contacts, but against every layer of the map that collides
+pub fn gather_map_contacts(
+    map: &LayeredMap,
+    sprite: &Sprite,
+    statics: &[Sprite],
+    into: &mut Vec<Contact>,
+) {
+    gather_sprite_contacts(sprite, statics, into);
+    for (layer, tilemap) in map.colliding() {
+        gather_tile_contacts(layer, tilemap, sprite, into);
+    }
+}
+
+fn gather_sprite_contacts(sprite: &Sprite, statics: &[Sprite], into: &mut Vec<Contact>) {
     // collide the player against other sprites
     for (bi, b) in statics.iter().enumerate() {
         if let Some(disp) = Rect::rect_displacement(
@@ -52,6 +72,9 @@ pub fn gather_contacts(
         }
     }
 
+}
+
+fn gather_tile_contacts(layer: usize, tilemap: &Tilemap, sprite: &Sprite, into: &mut Vec<Contact>) {
     // collide mobiles against walls
     // Checks tiles at the corners
     let corners = vec![
@@ -70,11 +93,7 @@ pub fn gather_contacts(
         ),
     ];
     for (x, y) in corners {
-        if x >= tilemap.position.0
-            && x < tilemap.position.0 + (TILE_SZ * tilemap.size().0) as i32
-            && y >= tilemap.position.1
-            && y < tilemap.position.1 + (TILE_SZ * tilemap.size().1) as i32
-        {
+        if tilemap.contains(Vec2i(x, y)) {
             let (tile, pos) = tilemap.tile_at(Vec2i(x, y));
             if tile.solid {
                 // Treat the tile and sprite as rectangles
@@ -84,18 +103,13 @@ pub fn gather_contacts(
                     w: sprite.get_dimensions().0 as u16,
                     h: sprite.get_dimensions().1 as u16,
                 };
-                let rect = Rect {
-                    // position on the whole map
-                    x: pos.0 * TILE_SZ as i32 + tilemap.position.0,
-                    y: pos.1 * TILE_SZ as i32 + tilemap.position.1,
-                    w: TILE_SZ as u16,
-                    h: TILE_SZ as u16,
-                };
+                // position on the whole map
+                let rect = tilemap.tile_rect(pos);
                 let mtv = Rect::rect_displacement(a_rect, rect);
                 if let Some(m) = mtv {
                     into.push(Contact {
                         a: ColliderID::Dynamic(0),
-                        b: ColliderID::Static((0, Vec2i(x, y), false)),
+                        b: ColliderID::Static((layer, Vec2i(x, y), false)),
                         mtv: Some(m),
                         effect: tile.collide,
                     });
@@ -103,7 +117,7 @@ pub fn gather_contacts(
             } else {
                 into.push(Contact {
                     a: ColliderID::Dynamic(0),
-                    b: ColliderID::Static((0, Vec2i(x, y), false)),
+                    b: ColliderID::Static((layer, Vec2i(x, y), false)),
                     mtv: None,
                     effect: tile.collide,
                 });
@@ -117,6 +131,39 @@ pub fn restitute(
     sprite: &mut Sprite,
     statics: &[Sprite],
     contacts: &mut [Contact],
+) -> Effect {
+    restitute_with(
+        |_, pos| tilemap.tile_rect(tilemap.tile_at(pos).1),
+        sprite,
+        statics,
+        contacts,
+    )
+}
+
+/// Like restitute, for contacts from gather_map_contacts
+pub fn restitute_map(
+    map: &LayeredMap,
+    sprite: &mut Sprite,
+    statics: &[Sprite],
+    contacts: &mut [Contact],
+) -> Effect {
+    restitute_with(
+        |layer, pos| {
+            let tilemap = &map.layers()[layer].tilemap;
+            tilemap.tile_rect(tilemap.tile_at(pos).1)
+        },
+        sprite,
+        statics,
+        contacts,
+    )
+}
+
+/// `tile_rect` finds the rect of the tile a contact hit from its layer and position
+fn restitute_with(
+    tile_rect: impl Fn(usize, Vec2i) -> Rect,
+    sprite: &mut Sprite,
+    statics: &[Sprite],
+    contacts: &mut [Contact],
 ) -> Effect {
     // handle restitution of dynamics against statics wrt contacts.
     // Assuming everything is rectangles
@@ -137,7 +184,6 @@ pub fn restitute(
         if let Some(mtv) = contact.mtv {
             if let ColliderID::Dynamic(i) = contact.a {
                 if let ColliderID::Static(si) = contact.b {
-                    let tile = tilemap.tile_at(si.1);
                     let a_rect = Rect {
                         x: sprite.position.0,
                         y: sprite.position.1,
@@ -152,12 +198,7 @@ pub fn restitute(
                             h: statics[si.0].frame.h,
                         }
                     } else {
-                        Rect {
-                            x: tile.1 .0 * TILE_SZ as i32 + tilemap.position.0,
-                            y: tile.1 .1 * TILE_SZ as i32 + tilemap.position.1,
-                            w: TILE_SZ as u16,
-                            h: TILE_SZ as u16,
-                        }
+                        tile_rect(si.0, si.1)
                     };
 
                     if Rect::rect_touching(a_rect, rect) {
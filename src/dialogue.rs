@@ -0,0 +1,267 @@
+use crate::screen::{Blend, Filter, Screen};
+use crate::text::{DrawTextExt, Fonts, TextFormat};
+use crate::texture::Texture;
+use crate::types::{Rect, Rgba, Vec2i};
+use std::rc::Rc;
+
+/// Space between the edge of the dialogue box and what's inside it
+const PADDING: i32 = 6;
+
+/// A picture of whoever is talking, e.g. a dino's standing frame
+#[derive(Clone)]
+pub struct Portrait {
+    pub image: Rc<Texture>,
+    pub frame: Rect,
+}
+impl Portrait {
+    pub fn new(image: &Rc<Texture>, frame: Rect) -> Self {
+        Self {
+            image: Rc::clone(image),
+            frame,
+        }
+    }
+}
+
+/// One thing someone says
+#[derive(Clone)]
+pub struct Message {
+    pub speaker: Option<String>,
+    pub portrait: Option<Portrait>,
+    pub text: String,
+}
+impl Message {
+    pub fn new(text: &str) -> Self {
+        Self {
+            speaker: None,
+            portrait: None,
+            text: text.to_string(),
+        }
+    }
+    pub fn with_speaker(self, speaker: &str, portrait: Option<Portrait>) -> Self {
+        Self {
+            speaker: Some(speaker.to_string()),
+            portrait,
+            ..self
+        }
+    }
+}
+
+/// As much of a message as fits in the box at once
+struct Page {
+    message: usize,
+    lines: Vec<String>,
+    chars: usize,
+}
+
+/// A conversation shown in a box, a page at a time.
+/// Call `update` every frame to type out the text and `advance` when the player presses a key.
+pub struct Dialogue {
+    messages: Vec<Message>,
+    pages: Vec<Page>,
+    page: usize,
+    /// How many characters of the current page are showing
+    revealed: f32,
+    /// Where to draw the box, in world coordinates
+    pub rect: Rect,
+    pub format: TextFormat,
+    pub speaker_color: Rgba,
+    pub background: Rgba,
+    pub border: Rgba,
+    /// How many characters appear each frame
+    pub chars_per_frame: f32,
+}
+
+impl Dialogue {
+    /**
+     * Sets up a conversation and splits it into pages that fit the box
+     *
+     * Params:
+     *   - fonts: the font registry, to measure text with
+     *   - messages: what gets said, in order
+     *   - rect: the box to show it in, in world coordinates
+     *   - format: font, size and color of the text
+     **/
+    pub fn new(fonts: &Fonts, messages: Vec<Message>, rect: Rect, format: TextFormat) -> Self {
+        let mut dialogue = Self {
+            messages,
+            pages: vec![],
+            page: 0,
+            revealed: 0.0,
+            rect,
+            format,
+            speaker_color: Rgba(250, 30, 10, 255),
+            background: Rgba(53, 40, 33, 255),
+            border: Rgba(110, 50, 20, 255),
+            chars_per_frame: 0.5,
+        };
+        dialogue.paginate(fonts);
+        dialogue
+    }
+    pub fn with_speed(self, chars_per_frame: f32) -> Self {
+        Self {
+            chars_per_frame,
+            ..self
+        }
+    }
+    pub fn with_colors(self, background: Rgba, border: Rgba, speaker_color: Rgba) -> Self {
+        Self {
+            background,
+            border,
+            speaker_color,
+            ..self
+        }
+    }
+    /// Where the portrait and the text go for a message
+    fn regions(&self, message: &Message) -> (Option<Rect>, Rect) {
+        let inner = Rect {
+            x: self.rect.x + PADDING,
+            y: self.rect.y + PADDING,
+            w: (self.rect.w as i32 - 2 * PADDING).max(0) as u16,
+            h: (self.rect.h as i32 - 2 * PADDING).max(0) as u16,
+        };
+        match message.portrait {
+            Some(_) => {
+                // A square on the left as tall as the box
+                let side = inner.h.min(inner.w);
+                let portrait = Rect { w: side, h: side, ..inner };
+                let text = Rect {
+                    x: inner.x + side as i32 + PADDING,
+                    w: (inner.w as i32 - side as i32 - PADDING).max(0) as u16,
+                    ..inner
+                };
+                (Some(portrait), text)
+            }
+            None => (None, inner),
+        }
+    }
+    /// Wrap every message to the box and cut it into pages
+    fn paginate(&mut self, fonts: &Fonts) {
+        let line_height = fonts.line_height(self.format.font, self.format.size);
+        self.pages.clear();
+        for (i, message) in self.messages.iter().enumerate() {
+            let (_, text) = self.regions(message);
+            let mut rows = (text.h as f32 / line_height) as usize;
+            if message.speaker.is_some() {
+                // The speaker's name takes up the first row
+                rows = rows.saturating_sub(1);
+            }
+            let lines = fonts.wrap_text(&message.text, &self.format, Some(text.w as f32));
+            for lines in lines.chunks(rows.max(1)) {
+                self.pages.push(Page {
+                    message: i,
+                    chars: lines.iter().map(|l| l.chars().count()).sum(),
+                    lines: lines.to_vec(),
+                });
+            }
+        }
+        self.page = 0;
+        self.revealed = 0.0;
+    }
+    /// Type out a little more of the current page
+    pub fn update(&mut self) {
+        if let Some(page) = self.pages.get(self.page) {
+            self.revealed = (self.revealed + self.chars_per_frame).min(page.chars as f32);
+        }
+    }
+    /// Whether the whole current page is showing, so it's waiting for a key
+    pub fn page_done(&self) -> bool {
+        match self.pages.get(self.page) {
+            Some(page) => self.revealed as usize >= page.chars,
+            None => true,
+        }
+    }
+    /// Show the rest of the page if it's still typing, otherwise go to the next page
+    pub fn advance(&mut self) {
+        if !self.page_done() {
+            self.revealed = self.pages[self.page].chars as f32;
+        } else if self.page < self.pages.len() {
+            self.page += 1;
+            self.revealed = 0.0;
+        }
+    }
+    /// Whether every page has been read
+    pub fn finished(&self) -> bool {
+        self.page >= self.pages.len()
+    }
+    /// Start the conversation over
+    pub fn restart(&mut self) {
+        self.page = 0;
+        self.revealed = 0.0;
+    }
+    /// The message being shown, if any
+    pub fn message(&self) -> Option<&Message> {
+        self.pages.get(self.page).map(|p| &self.messages[p.message])
+    }
+    /// The lines of the current page cut off after the revealed characters
+    fn visible_text(&self) -> String {
+        let page = match self.pages.get(self.page) {
+            Some(page) => page,
+            None => return String::new(),
+        };
+        let mut left = self.revealed as usize;
+        let mut shown = vec![];
+        for line in page.lines.iter() {
+            let n = line.chars().count().min(left);
+            shown.push(line.chars().take(n).collect::<String>());
+            left -= n;
+            if left == 0 {
+                break;
+            }
+        }
+        shown.join("\n")
+    }
+}
+
+pub trait DrawDialogueExt {
+    fn draw_dialogue(&mut self, fonts: &mut Fonts, dialogue: &Dialogue);
+}
+
+impl<'fb> DrawDialogueExt for Screen<'fb> {
+    fn draw_dialogue(&mut self, fonts: &mut Fonts, dialogue: &Dialogue) {
+        let message = match dialogue.message() {
+            Some(message) => message,
+            None => return,
+        };
+        self.rect(dialogue.rect, dialogue.background);
+        self.empty_rect(dialogue.rect, 2, dialogue.border);
+        let (portrait_rect, mut text_rect) = dialogue.regions(message);
+        if let (Some(portrait), Some(to)) = (&message.portrait, portrait_rect) {
+            // Scale the frame up as far as it fits, keeping its shape
+            let scale = (to.w as f32 / portrait.frame.w as f32).min(to.h as f32 / portrait.frame.h as f32);
+            let w = (portrait.frame.w as f32 * scale) as u16;
+            let h = (portrait.frame.h as f32 * scale) as u16;
+            let to = Rect {
+                x: to.x + (to.w - w) as i32 / 2,
+                y: to.y + (to.h - h) as i32 / 2,
+                w,
+                h,
+            };
+            self.bitblt_scaled(&portrait.image, portrait.frame, to, Filter::Nearest, Blend::default());
+        }
+        let line_height = fonts.line_height(dialogue.format.font, dialogue.format.size);
+        if let Some(speaker) = &message.speaker {
+            let name_format = TextFormat {
+                color: dialogue.speaker_color,
+                ..dialogue.format
+            };
+            self.draw_text_box(fonts, speaker, text_rect, &name_format);
+            text_rect.y += line_height as i32;
+            text_rect.h = text_rect.h.saturating_sub(line_height as u16);
+        }
+        // The page was wrapped already, so don't wrap the partial text differently
+        let format = dialogue.format.with_wrap(false);
+        self.draw_text_box(fonts, &dialogue.visible_text(), text_rect, &format);
+        if dialogue.page_done() {
+            // A little arrow in the corner while it's waiting for a key
+            let r = dialogue.rect;
+            let x = r.x + r.w as i32 - PADDING - 6;
+            let y = r.y + r.h as i32 - PADDING - 4;
+            self.triangle(
+                Vec2i(x, y),
+                Vec2i(x + 6, y),
+                Vec2i(x + 3, y + 3),
+                dialogue.speaker_color,
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::screen::{Blend, Screen};
+use crate::tiles::Tilemap;
+use crate::types::Vec2i;
+
+/// One named tile layer in a LayeredMap
+#[derive(Clone)]
+pub struct MapLayer {
+    pub name: String,
+    pub tilemap: Tilemap,
+    /// Lower layers are drawn first; change it with `LayeredMap::set_z` so the map stays in order
+    z: i32,
+    pub visible: bool,
+    /// 0.0 to 1.0
+    pub opacity: f32,
+    /// How much the layer scrolls with the camera on each axis:
+    /// 1.0 moves with the world, less looks further away, 0.0 stays put on screen
+    pub parallax: (f32, f32),
+    /// Whether its solid tiles stop sprites and its tiles' effects happen
+    pub collides: bool,
+    /// Drawn after the sprites, so they can go behind it
+    pub foreground: bool,
+}
+
+impl MapLayer {
+    /// A visible, opaque background layer that doesn't collide
+    pub fn new(name: &str, tilemap: Tilemap) -> Self {
+        Self {
+            name: name.to_string(),
+            tilemap,
+            z: 0,
+            visible: true,
+            opacity: 1.0,
+            parallax: (1.0, 1.0),
+            collides: false,
+            foreground: false,
+        }
+    }
+    pub fn with_z(self, z: i32) -> Self {
+        Self { z, ..self }
+    }
+    pub fn with_visible(self, visible: bool) -> Self {
+        Self { visible, ..self }
+    }
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        Self {
+            opacity: opacity.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+    pub fn with_parallax(self, x: f32, y: f32) -> Self {
+        Self {
+            parallax: (x, y),
+            ..self
+        }
+    }
+    pub fn with_collides(self, collides: bool) -> Self {
+        Self { collides, ..self }
+    }
+    pub fn with_foreground(self, foreground: bool) -> Self {
+        Self { foreground, ..self }
+    }
+    pub fn z(&self) -> i32 {
+        self.z
+    }
+    /// How far from its position the layer is drawn when the camera is at `camera`
+    pub fn parallax_offset(&self, Vec2i(cx, cy): Vec2i) -> Vec2i {
+        Vec2i(
+            (cx as f32 * (1.0 - self.parallax.0)).round() as i32,
+            (cy as f32 * (1.0 - self.parallax.1)).round() as i32,
+        )
+    }
+    /// Draw the layer as it is at frame `now` with its opacity and parallax, if it's visible
+    pub fn draw(&self, screen: &mut Screen, now: usize) {
+        if !self.visible || self.opacity <= 0.0 {
+            return;
+        }
+        let camera = screen.bounds();
+        let offset = self.parallax_offset(Vec2i(camera.x, camera.y));
+        let blend = Blend::default().with_opacity(self.opacity);
+        self.tilemap.draw_blend(screen, offset, blend, now);
+    }
+}
+
+/// Tile layers stacked up by z-order, split into ones drawn under the sprites and ones drawn over them
+#[derive(Clone, Default)]
+pub struct LayeredMap {
+    /// Sorted by z; layers with the same z stay in the order they were added
+    layers: Vec<MapLayer>,
+}
+
+impl LayeredMap {
+    pub fn new() -> Self {
+        Self { layers: vec![] }
+    }
+    /// Add a layer above every layer with the same or lower z
+    pub fn add(&mut self, layer: MapLayer) {
+        let at = self
+            .layers
+            .iter()
+            .position(|l| l.z > layer.z)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(at, layer);
+    }
+    pub fn with_layer(mut self, layer: MapLayer) -> Self {
+        self.add(layer);
+        self
+    }
+    /// Every layer, bottom first
+    pub fn layers(&self) -> &[MapLayer] {
+        &self.layers
+    }
+    pub fn layer(&self, name: &str) -> Option<&MapLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut MapLayer> {
+        self.layers.iter_mut().find(|l| l.name == name)
+    }
+    /// Move a layer to a new z, on top of the others already there
+    pub fn set_z(&mut self, name: &str, z: i32) {
+        if let Some(i) = self.layers.iter().position(|l| l.name == name) {
+            let layer = self.layers.remove(i);
+            self.add(layer.with_z(z));
+        }
+    }
+    /// The layers sprites collide with, with their indices in `layers()`
+    pub fn colliding(&self) -> impl Iterator<Item = (usize, &Tilemap)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.collides)
+            .map(|(i, l)| (i, &l.tilemap))
+    }
+    /// Draw the layers that go under the sprites; call this before drawing them
+    pub fn draw_background(&self, screen: &mut Screen, now: usize) {
+        for layer in self.layers.iter().filter(|l| !l.foreground) {
+            layer.draw(screen, now);
+        }
+    }
+    /// Draw the layers that go over the sprites; call this after drawing them
+    pub fn draw_foreground(&self, screen: &mut Screen, now: usize) {
+        for layer in self.layers.iter().filter(|l| l.foreground) {
+            layer.draw(screen, now);
+        }
+    }
+}
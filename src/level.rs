@@ -0,0 +1,308 @@
+use crate::animation::AnimationState;
+use crate::assets::{Assets, Handle};
+use crate::atlas::Atlas;
+use crate::layers::{LayeredMap, MapLayer};
+use crate::sprite::Sprite;
+use crate::tiled::{load_tileset, TiledError, TiledMap};
+use crate::tiles::{Tilemap, NO_TILE};
+use crate::types::{Effect, Rect, Vec2i};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Levels are JSON files. The tiles can come from a Tiled map, from layers written in
+// the level file, or both; everything else (who's where, the way out) is in the level file.
+
+/// A tile layer written out in a level file
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct LayerData {
+    pub name: String,
+    /// A Tiled tileset file, relative to the assets' root
+    pub tileset: String,
+    #[serde(default = "origin")]
+    pub position: Vec2i,
+    /// Size in tiles
+    pub width: usize,
+    pub height: usize,
+    /// Row by row; null is an empty cell
+    pub tiles: Vec<Option<usize>>,
+    /// Draw order among all the level's layers
+    #[serde(default)]
+    pub z: i32,
+    #[serde(default = "yes")]
+    pub visible: bool,
+    #[serde(default = "one")]
+    pub opacity: f32,
+    /// How much it scrolls with the camera; 1.0 moves with the world
+    #[serde(default = "no_parallax")]
+    pub parallax: (f32, f32),
+    /// Whether the player bumps into its solid tiles
+    #[serde(default)]
+    pub collides: bool,
+    /// Whether it's drawn over the sprites
+    #[serde(default)]
+    pub foreground: bool,
+}
+
+/// A sprite placed in a level, like an enemy or the gem
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Spawn {
+    pub name: String,
+    /// A frame in the atlas, or an image file relative to the assets' root if `frame` is given
+    pub image: String,
+    #[serde(default)]
+    pub frame: Option<Rect>,
+    pub position: Vec2i,
+    /// How many lives it has, for things that get fought
+    #[serde(default)]
+    pub health: usize,
+    /// What touching it does
+    #[serde(default = "nothing")]
+    pub effect: Effect,
+}
+
+/// Walking into `rect` takes the player to another level
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Exit {
+    pub rect: Rect,
+    /// The name of the level it goes to
+    pub to: String,
+}
+
+/// Everything about a level that gets saved
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct LevelData {
+    pub name: String,
+    /// A Tiled map with tile layers, relative to the assets' root
+    #[serde(default)]
+    pub map: Option<String>,
+    /// Tile layers drawn on top of the map's
+    #[serde(default)]
+    pub layers: Vec<LayerData>,
+    #[serde(default)]
+    pub enemies: Vec<Spawn>,
+    /// Other sprites, like pickups
+    #[serde(default)]
+    pub items: Vec<Spawn>,
+    #[serde(default)]
+    pub exits: Vec<Exit>,
+    pub player_spawn: Vec2i,
+    /// The camera never shows anything outside this, in world coordinates
+    pub camera_bounds: Rect,
+}
+
+fn origin() -> Vec2i {
+    Vec2i(0, 0)
+}
+fn nothing() -> Effect {
+    Effect::Nothing
+}
+fn yes() -> bool {
+    true
+}
+fn one() -> f32 {
+    1.0
+}
+fn no_parallax() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+impl LevelData {
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        let text = fs::read_to_string(path).map_err(|e| LevelError::Io(path.to_path_buf(), e))?;
+        serde_json::from_str(&text).map_err(|e| LevelError::Json(path.to_path_buf(), e))
+    }
+    pub fn save(&self, path: &Path) -> Result<(), LevelError> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| LevelError::Json(path.to_path_buf(), e))?;
+        fs::write(path, text).map_err(|e| LevelError::Io(path.to_path_buf(), e))
+    }
+}
+
+/// A level ready to play: its data plus the tilemaps and sprites made from it
+pub struct Level {
+    pub data: LevelData,
+    /// The map's tile layers and the level file's; at the same z the level file's go on top
+    pub map: LayeredMap,
+    /// The enemies' sprites then the items', in the same order as in `data`
+    pub sprites: Vec<Sprite>,
+}
+
+impl Level {
+    /**
+     * Makes the tilemaps and sprites for a level
+     *
+     * Params:
+     *   - assets: where tilesets, maps and images get loaded from
+     *   - atlas: has the frames spawns can refer to by name
+     *   - data: the level
+     **/
+    pub fn build<L>(
+        assets: &mut Assets<L>,
+        atlas: &Atlas,
+        data: LevelData,
+    ) -> Result<Self, LevelError> {
+        let mut map = match &data.map {
+            Some(map) => TiledMap::load(assets, map)?.layered(),
+            None => LayeredMap::new(),
+        };
+        for layer in data.layers.iter() {
+            let tileset = load_tileset(assets, &layer.tileset)?;
+            if layer.tiles.len() != layer.width * layer.height {
+                return Err(LevelError::Invalid(
+                    data.name.clone(),
+                    format!("layer {} is the wrong size", layer.name),
+                ));
+            }
+            if let Some(id) = layer
+                .tiles
+                .iter()
+                .flatten()
+                .find(|&&id| id >= tileset.len())
+            {
+                return Err(LevelError::Invalid(
+                    data.name.clone(),
+                    format!(
+                        "layer {} uses tile {}, which {} doesn't have",
+                        layer.name, id, layer.tileset
+                    ),
+                ));
+            }
+            let tiles = layer.tiles.iter().map(|t| t.unwrap_or(NO_TILE)).collect();
+            let tilemap = Tilemap::new(layer.position, (layer.width, layer.height), &tileset, tiles);
+            map.add(
+                MapLayer::new(&layer.name, tilemap)
+                    .with_z(layer.z)
+                    .with_visible(layer.visible)
+                    .with_opacity(layer.opacity)
+                    .with_parallax(layer.parallax.0, layer.parallax.1)
+                    .with_collides(layer.collides)
+                    .with_foreground(layer.foreground),
+            );
+        }
+        let mut sprites = vec![];
+        for spawn in data.enemies.iter().chain(data.items.iter()) {
+            let (image, frame) = match spawn.frame {
+                Some(frame) => {
+                    // A missing image's stand-in is made just big enough for the frame
+                    let (w, h) = (
+                        frame.x as usize + frame.w as usize,
+                        frame.y as usize + frame.h as usize,
+                    );
+                    let handle = assets.load_texture_or_missing(&spawn.image, w, h);
+                    (Rc::clone(assets.texture(handle)), frame)
+                }
+                None => match atlas.get(&spawn.image) {
+                    Some(frame) => (Rc::clone(atlas.texture()), frame),
+                    None => {
+                        return Err(LevelError::Invalid(
+                            data.name.clone(),
+                            format!(
+                                "{} uses {}, which isn't in the atlas",
+                                spawn.name, spawn.image
+                            ),
+                        ))
+                    }
+                },
+            };
+            sprites.push(Sprite::new(
+                &image,
+                frame,
+                spawn.position,
+                true,
+                0,
+                0,
+                AnimationState::Nothing,
+                spawn.effect,
+            ));
+        }
+        Ok(Self { data, map, sprites })
+    }
+    /**
+     * Loads a level file and every level its exits lead to, registering them in `assets` by name
+     *
+     * Params:
+     *   - assets: where the levels and everything they use get loaded into
+     *   - atlas: has the frames spawns can refer to by name
+     *   - dir: where the level files are, relative to the assets' root; each is `<name>.json`
+     *   - first: the name of the level to start from
+     *
+     * Returns the handle of the first level
+     **/
+    pub fn load_all(
+        assets: &mut Assets<Level>,
+        atlas: &Atlas,
+        dir: &str,
+        first: &str,
+    ) -> Result<Handle<Level>, LevelError> {
+        let mut to_load = vec![first.to_string()];
+        while let Some(name) = to_load.pop() {
+            if assets.find_level(&name).is_some() {
+                continue;
+            }
+            let path = assets.root().join(dir).join(format!("{}.json", name));
+            let data = LevelData::load(&path)?;
+            if data.name != name {
+                return Err(LevelError::Invalid(
+                    name,
+                    format!("{} says it's called {}", path.display(), data.name),
+                ));
+            }
+            to_load.extend(data.exits.iter().map(|e| e.to.clone()));
+            let level = Level::build(assets, atlas, data)?;
+            assets.add_level(&name, level);
+        }
+        Ok(assets.find_level(first).unwrap())
+    }
+    pub fn name(&self) -> &str {
+        &self.data.name
+    }
+    /// The first enemy and its sprite
+    pub fn enemy(&self) -> Option<(&Spawn, &Sprite)> {
+        self.data.enemies.first().map(|e| (e, &self.sprites[0]))
+    }
+    /// The exit the player is standing in, if any
+    pub fn exit_at(&self, pos: Vec2i) -> Option<&Exit> {
+        self.data.exits.iter().find(|e| e.rect.contains(pos))
+    }
+    /// Move a camera position so a view of the given size stays inside the camera bounds
+    pub fn clamp_camera(&self, Vec2i(x, y): Vec2i, (view_w, view_h): (usize, usize)) -> Vec2i {
+        let b = self.data.camera_bounds;
+        // If the bounds are smaller than the view, line up their top left corners
+        let max_x = (b.x + b.w as i32 - view_w as i32).max(b.x);
+        let max_y = (b.y + b.h as i32 - view_h as i32).max(b.y);
+        Vec2i(x.clamp(b.x, max_x), y.clamp(b.y, max_y))
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+    Map(TiledError),
+    /// The level's data doesn't fit together, e.g. a spawn uses a frame that doesn't exist
+    Invalid(String, String),
+}
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(path, e) => {
+                write!(f, "couldn't read or write level {}: {}", path.display(), e)
+            }
+            LevelError::Json(path, e) => {
+                write!(f, "couldn't parse level {}: {}", path.display(), e)
+            }
+            LevelError::Map(e) => write!(f, "couldn't load level map: {}", e),
+            LevelError::Invalid(name, e) => write!(f, "level {} is broken: {}", name, e),
+        }
+    }
+}
+impl std::error::Error for LevelError {}
+impl From<TiledError> for LevelError {
+    fn from(e: TiledError) -> Self {
+        LevelError::Map(e)
+    }
+}
@@ -1,9 +1,18 @@
 pub mod animation;
+pub mod atlas;
+pub mod assets;
+pub mod bitmap_font;
+pub mod canvas;
 pub mod collision;
+pub mod dialogue;
 pub mod health;
+pub mod layers;
+pub mod level;
+pub mod markup;
 pub mod screen;
 pub mod sprite;
 pub mod text;
 pub mod texture;
+pub mod tiled;
 pub mod tiles;
 pub mod types;
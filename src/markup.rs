@@ -0,0 +1,453 @@
+use crate::screen::Screen;
+use crate::sprite::Sprite;
+use crate::text::{Fonts, Overflow, TextFormat};
+use crate::texture::Texture;
+use crate::types::{Rect, Rgba, Vec2i};
+use fontdue::layout::{
+    CoordinateSystem, HorizontalAlign, Layout, LayoutSettings, TextStyle, VerticalAlign,
+};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Markup is plain text with a few tags mixed in:
+//   [color=#f80]...[/color]  draws the text in between in a color (#rgb, #rgba, #rrggbb or #rrggbbaa)
+//   [icon=heart]             draws a registered icon inline, sitting on the baseline
+//   [[                       a literal [
+// Anything else in brackets (like "[a]ggressive") is left alone.
+
+/// A run of parsed markup
+#[derive(PartialEq, Clone, Debug)]
+pub enum Span {
+    /// Text in a color, or the default color if None
+    Text { text: String, color: Option<Rgba> },
+    Icon(String),
+}
+
+/// Split markup into text runs and icons
+pub fn parse(markup: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut colors: Vec<Rgba> = vec![];
+    let mut text = String::new();
+    let mut rest = markup;
+    while let Some(c) = rest.chars().next() {
+        if c == '[' {
+            if rest.starts_with("[[") {
+                text.push('[');
+                rest = &rest[2..];
+                continue;
+            }
+            if let Some(end) = rest.find(']') {
+                let tag = &rest[1..end];
+                let color = tag.strip_prefix("color=").and_then(parse_color);
+                let icon = tag.strip_prefix("icon=");
+                let known =
+                    color.is_some() || (tag == "/color" && !colors.is_empty()) || icon.is_some();
+                if known {
+                    if !text.is_empty() {
+                        spans.push(Span::Text {
+                            text: std::mem::take(&mut text),
+                            color: colors.last().copied(),
+                        });
+                    }
+                    if let Some(color) = color {
+                        colors.push(color);
+                    } else if let Some(icon) = icon {
+                        spans.push(Span::Icon(icon.to_string()));
+                    } else {
+                        colors.pop();
+                    }
+                    rest = &rest[(end + 1)..];
+                    continue;
+                }
+            }
+        }
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !text.is_empty() {
+        spans.push(Span::Text {
+            text,
+            color: colors.last().copied(),
+        });
+    }
+    spans
+}
+
+/// Parse a hex color like #ff0 or #ffcc00aa
+pub fn parse_color(hex: &str) -> Option<Rgba> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..(i + 1)], 16).unwrap() * 17;
+    let pair = |i: usize| u8::from_str_radix(&hex[i..(i + 2)], 16).unwrap();
+    match hex.len() {
+        3 => Some(Rgba(digit(0), digit(1), digit(2), 255)),
+        4 => Some(Rgba(digit(0), digit(1), digit(2), digit(3))),
+        6 => Some(Rgba(pair(0), pair(2), pair(4), 255)),
+        8 => Some(Rgba(pair(0), pair(2), pair(4), pair(6))),
+        _ => None,
+    }
+}
+
+/// Named texture frames that markup can draw with [icon=name]
+#[derive(Default)]
+pub struct Icons {
+    icons: HashMap<String, (Rc<Texture>, Rect)>,
+}
+impl Icons {
+    pub fn new() -> Self {
+        Self {
+            icons: HashMap::new(),
+        }
+    }
+    pub fn add(&mut self, name: &str, image: &Rc<Texture>, frame: Rect) {
+        self.icons.insert(name.to_string(), (Rc::clone(image), frame));
+    }
+    /// Use a sprite's current frame as an icon
+    pub fn add_sprite(&mut self, name: &str, sprite: &Sprite) {
+        self.add(name, &sprite.image, sprite.frame);
+    }
+    pub fn get(&self, name: &str) -> Option<&(Rc<Texture>, Rect)> {
+        self.icons.get(name)
+    }
+}
+
+/// One thing to lay out on a line
+enum Atom {
+    Word { text: String, color: Rgba, width: f32 },
+    Space(f32),
+    Icon { image: Rc<Texture>, frame: Rect },
+    Break,
+}
+impl Atom {
+    fn width(&self) -> f32 {
+        match self {
+            Atom::Word { width, .. } | Atom::Space(width) => *width,
+            Atom::Icon { frame, .. } => frame.w as f32,
+            Atom::Break => 0.0,
+        }
+    }
+}
+
+struct Line {
+    atoms: Vec<Atom>,
+    width: f32,
+    /// Distance from the top of the line to the baseline
+    ascent: f32,
+}
+
+/// Font ascent, descent (as a positive distance) and the gap between lines
+fn line_metrics(fonts: &Fonts, format: &TextFormat) -> (f32, f32, f32) {
+    let line_height = fonts.line_height(format.font, format.size);
+    match fonts
+        .get(format.font)
+        .and_then(|f| f.horizontal_line_metrics(format.size))
+    {
+        Some(m) => (m.ascent, -m.descent, line_height - (m.ascent - m.descent)),
+        None => (format.size * 0.8, format.size * 0.2, 0.0),
+    }
+}
+
+/// Break markup into lines no wider than max_width (if wrapping)
+fn lay_out(
+    fonts: &Fonts,
+    icons: &Icons,
+    markup: &str,
+    format: &TextFormat,
+    max_width: Option<f32>,
+) -> Vec<Line> {
+    let (ascent, _, _) = line_metrics(fonts, format);
+    let max_width = if format.wrap { max_width } else { None };
+    let space = fonts.text_width(format.font, format.size, " ");
+    // First turn the spans into words, spaces, icons and line breaks
+    let mut atoms = vec![];
+    for span in parse(markup) {
+        match span {
+            Span::Text { text, color } => {
+                let color = color.unwrap_or(format.color);
+                for (i, paragraph) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        atoms.push(Atom::Break);
+                    }
+                    for (j, word) in paragraph.split(' ').enumerate() {
+                        if j > 0 {
+                            atoms.push(Atom::Space(space));
+                        }
+                        if !word.is_empty() {
+                            atoms.push(Atom::Word {
+                                text: word.to_string(),
+                                color,
+                                width: fonts.text_width(format.font, format.size, word),
+                            });
+                        }
+                    }
+                }
+            }
+            Span::Icon(name) => match icons.get(&name) {
+                Some((image, frame)) => atoms.push(Atom::Icon {
+                    image: Rc::clone(image),
+                    frame: *frame,
+                }),
+                // Show missing icons instead of quietly dropping them
+                None => {
+                    let text = format!("[icon={}]", name);
+                    atoms.push(Atom::Word {
+                        width: fonts.text_width(format.font, format.size, &text),
+                        text,
+                        color: format.color,
+                    });
+                }
+            },
+        }
+    }
+    // Then fill up lines greedily, breaking at spaces
+    let new_line = || Line {
+        atoms: vec![],
+        width: 0.0,
+        ascent,
+    };
+    let mut lines = vec![];
+    let mut line = new_line();
+    let mut pending_space = 0.0;
+    for atom in atoms {
+        match atom {
+            Atom::Break => {
+                lines.push(std::mem::replace(&mut line, new_line()));
+                pending_space = 0.0;
+            }
+            Atom::Space(w) => pending_space += w,
+            atom => {
+                let w = atom.width();
+                let fits = match max_width {
+                    Some(max) => line.width + pending_space + w <= max,
+                    None => true,
+                };
+                if !fits && !line.atoms.is_empty() {
+                    lines.push(std::mem::replace(&mut line, new_line()));
+                } else if pending_space > 0.0 && !line.atoms.is_empty() {
+                    line.atoms.push(Atom::Space(pending_space));
+                    line.width += pending_space;
+                }
+                pending_space = 0.0;
+                if let Atom::Icon { frame, .. } = &atom {
+                    // Tall icons push the baseline down
+                    line.ascent = line.ascent.max(frame.h as f32);
+                }
+                line.width += w;
+                line.atoms.push(atom);
+            }
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+fn line_height(line: &Line, descent: f32, gap: f32) -> f32 {
+    line.ascent + descent + gap
+}
+
+/// Width and height of markup laid out with `format`, wrapped to `max_width` if given
+pub fn measure_rich_text(
+    fonts: &Fonts,
+    icons: &Icons,
+    markup: &str,
+    format: &TextFormat,
+    max_width: Option<f32>,
+) -> (f32, f32) {
+    let (_, descent, gap) = line_metrics(fonts, format);
+    let lines = lay_out(fonts, icons, markup, format, max_width);
+    let w = lines.iter().map(|l| l.width).fold(0.0, f32::max);
+    let h = lines.iter().map(|l| line_height(l, descent, gap)).sum();
+    (w, h)
+}
+
+pub trait DrawRichTextExt {
+    fn draw_rich_text(
+        &mut self,
+        fonts: &mut Fonts,
+        icons: &Icons,
+        markup: &str,
+        rect: Rect,
+        format: &TextFormat,
+    );
+}
+
+impl<'fb> DrawRichTextExt for Screen<'fb> {
+    /**
+     * Draws markup with inline colors and icons, wrapped and aligned inside a rect
+     *
+     * Params:
+     *   - fonts: the font registry
+     *   - icons: icons the markup can refer to
+     *   - markup: what to write
+     *   - rect: the box to put it in, in world coordinates
+     *   - format: font, size, default color, alignment and wrapping
+     *
+     * Note: Overflow::Ellipsis clips just like Overflow::Clip here.
+     * Icons are still drawn if the font isn't in `fonts`, but the text isn't.
+     **/
+    fn draw_rich_text(
+        &mut self,
+        fonts: &mut Fonts,
+        icons: &Icons,
+        markup: &str,
+        rect: Rect,
+        format: &TextFormat,
+    ) {
+        let (ascent, descent, gap) = line_metrics(fonts, format);
+        let lines = lay_out(fonts, icons, markup, format, Some(rect.w as f32));
+        let height: f32 = lines.iter().map(|l| line_height(l, descent, gap)).sum();
+        let mut y = rect.y as f32
+            + match format.v_align {
+                VerticalAlign::Top => 0.0,
+                VerticalAlign::Middle => (rect.h as f32 - height) / 2.0,
+                VerticalAlign::Bottom => rect.h as f32 - height,
+            };
+        if format.overflow != Overflow::Visible {
+            self.push_clip(rect);
+        }
+        let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+        for line in lines.iter() {
+            let baseline = y + line.ascent;
+            let mut x = rect.x as f32
+                + match format.h_align {
+                    HorizontalAlign::Left => 0.0,
+                    HorizontalAlign::Center => (rect.w as f32 - line.width) / 2.0,
+                    HorizontalAlign::Right => rect.w as f32 - line.width,
+                };
+            for atom in line.atoms.iter() {
+                match atom {
+                    Atom::Word { .. } if fonts.get(format.font).is_none() => {}
+                    Atom::Word { text, color, .. } => {
+                        // The layout puts the baseline one font ascent below y
+                        layout.reset(&LayoutSettings {
+                            x: x.floor(),
+                            y: (baseline - ascent).floor(),
+                            ..LayoutSettings::default()
+                        });
+                        layout.append(
+                            &fonts.font_list,
+                            &TextStyle::new(text, format.size, format.font.0),
+                        );
+                        self.draw_text(fonts, &mut layout, *color);
+                    }
+                    Atom::Icon { image, frame } => {
+                        // Icons sit on the baseline
+                        let y = (baseline - frame.h as f32).floor();
+                        self.bitblt(image, *frame, Vec2i(x.floor() as i32, y as i32), false);
+                    }
+                    Atom::Space(_) | Atom::Break => {}
+                }
+                x += atom.width();
+            }
+            y += line_height(line, descent, gap);
+        }
+        if format.overflow != Overflow::Visible {
+            self.pop_clip();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba = Rgba(255, 0, 0, 255);
+    const BLUE: Rgba = Rgba(0, 0, 255, 255);
+
+    fn text(text: &str, color: Option<Rgba>) -> Span {
+        Span::Text {
+            text: text.to_string(),
+            color,
+        }
+    }
+
+    #[test]
+    fn plain_text_is_one_span() {
+        assert_eq!(parse("hello there"), vec![text("hello there", None)]);
+        assert_eq!(parse(""), vec![]);
+    }
+
+    #[test]
+    fn nested_colors_go_back_to_the_outer_one() {
+        assert_eq!(
+            parse("a[color=#f00]b[color=#00f]c[/color]d[/color]e"),
+            vec![
+                text("a", None),
+                text("b", Some(RED)),
+                text("c", Some(BLUE)),
+                text("d", Some(RED)),
+                text("e", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_color_runs_to_the_end() {
+        assert_eq!(
+            parse("a[color=#f00]b[color=#00f]c"),
+            vec![text("a", None), text("b", Some(RED)), text("c", Some(BLUE))]
+        );
+    }
+
+    #[test]
+    fn closing_a_color_that_isnt_open_is_text() {
+        assert_eq!(parse("a[/color]b"), vec![text("a[/color]b", None)]);
+        assert_eq!(
+            parse("[color=#f00]a[/color][/color]"),
+            vec![text("a", Some(RED)), text("[/color]", None)]
+        );
+    }
+
+    #[test]
+    fn double_bracket_is_a_literal_bracket() {
+        assert_eq!(parse("[[a]ggressive"), vec![text("[a]ggressive", None)]);
+        assert_eq!(parse("[[color=#f00]x"), vec![text("[color=#f00]x", None)]);
+        assert_eq!(parse("[color=#f00][[[/color]"), vec![text("[", Some(RED))]);
+    }
+
+    #[test]
+    fn unknown_tags_are_left_alone() {
+        assert_eq!(parse("[a]ggressive"), vec![text("[a]ggressive", None)]);
+        assert_eq!(parse("[b]old[/b]"), vec![text("[b]old[/b]", None)]);
+        assert_eq!(parse("[color=nope]x"), vec![text("[color=nope]x", None)]);
+        assert_eq!(parse("[color=#f00"), vec![text("[color=#f00", None)]);
+    }
+
+    #[test]
+    fn icons_split_the_text() {
+        assert_eq!(
+            parse("hp [icon=heart] x3"),
+            vec![
+                text("hp ", None),
+                Span::Icon("heart".to_string()),
+                text(" x3", None),
+            ]
+        );
+        // An icon in a colored run doesn't end the color
+        assert_eq!(
+            parse("[color=#f00]a[icon=gem]b[/color]"),
+            vec![
+                text("a", Some(RED)),
+                Span::Icon("gem".to_string()),
+                text("b", Some(RED)),
+            ]
+        );
+        assert_eq!(
+            parse("[icon=a][icon=b]"),
+            vec![Span::Icon("a".to_string()), Span::Icon("b".to_string())]
+        );
+    }
+
+    #[test]
+    fn colors_in_every_length() {
+        assert_eq!(parse_color("#f80"), Some(Rgba(255, 136, 0, 255)));
+        assert_eq!(parse_color("#f808"), Some(Rgba(255, 136, 0, 136)));
+        assert_eq!(parse_color("#ff8000"), Some(Rgba(255, 128, 0, 255)));
+        assert_eq!(parse_color("ff800080"), Some(Rgba(255, 128, 0, 128)));
+        assert_eq!(parse_color("#ff800"), None);
+        assert_eq!(parse_color("#ggg"), None);
+        assert_eq!(parse_color(""), None);
+    }
+}
@@ -1,15 +1,9 @@
-use fontdue::{
-    layout::{GlyphRasterConfig, Layout},
-    Font, Metrics,
-};
-use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    hash::{Hash, Hasher},
-};
+use fontdue::layout::Layout;
 
 // We can pull in definitions from elsewhere in the crate!
 use crate::sprite::Sprite;
-use crate::texture::Texture;
+use crate::text::Fonts;
+use crate::texture::{mul, Texture};
 use crate::types::{Rect, Rgba, Vec2i};
 
 use rand::random;
@@ -20,6 +14,10 @@ pub struct Screen<'fb> {
     height: usize,
     depth: usize,
     position: Vec2i,
+    /// Stack of clip rects in framebuffer coordinates, each inside the one below
+    clips: Vec<Rect>,
+    /// Camera positions and clip stack depths to go back to when viewports are popped
+    viewports: Vec<(Vec2i, usize)>,
 }
 impl<'fb> Screen<'fb> {
     pub fn wrap(
@@ -35,6 +33,8 @@ impl<'fb> Screen<'fb> {
             height,
             depth,
             position,
+            clips: vec![],
+            viewports: vec![],
         }
     }
     pub fn size(&self) -> (usize, usize) {
@@ -48,13 +48,81 @@ impl<'fb> Screen<'fb> {
             h: self.height as u16,
         }
     }
+    /// Only draw inside `r` (in world coordinates) until the matching `pop_clip`.
+    /// Clips nest, so this can only ever shrink the drawable area.
+    pub fn push_clip(&mut self, r: Rect) {
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        // Here's the translation
+        let x0 = (r.x - self.position.0).max(cx0).min(cx1);
+        let y0 = (r.y - self.position.1).max(cy0).min(cy1);
+        let x1 = (r.x - self.position.0 + r.w as i32).max(x0).min(cx1);
+        let y1 = (r.y - self.position.1 + r.h as i32).max(y0).min(cy1);
+        self.clips.push(Rect {
+            x: x0,
+            y: y0,
+            w: (x1 - x0) as u16,
+            h: (y1 - y0) as u16,
+        });
+    }
+    pub fn pop_clip(&mut self) {
+        let floor = self.viewports.last().map_or(0, |v| v.1 + 1);
+        assert!(self.clips.len() > floor, "pop_clip without a matching push_clip");
+        self.clips.pop();
+    }
+    /// Draw into part of the framebuffer with its own camera until the matching `pop_viewport`.
+    /// The region is in framebuffer coordinates and gets clipped to whatever is drawable now.
+    pub fn push_viewport(&mut self, viewport: Viewport) {
+        let Viewport { region, camera } = viewport;
+        self.viewports.push((self.position, self.clips.len()));
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        let x0 = region.x.max(cx0).min(cx1);
+        let y0 = region.y.max(cy0).min(cy1);
+        let x1 = (region.x + region.w as i32).max(x0).min(cx1);
+        let y1 = (region.y + region.h as i32).max(y0).min(cy1);
+        self.clips.push(Rect {
+            x: x0,
+            y: y0,
+            w: (x1 - x0) as u16,
+            h: (y1 - y0) as u16,
+        });
+        // The camera's position lands on the region's top left corner
+        self.position = Vec2i(camera.0 - region.x, camera.1 - region.y);
+    }
+    /// Go back to the camera and clips from before the last `push_viewport`
+    pub fn pop_viewport(&mut self) {
+        let (position, depth) = self
+            .viewports
+            .pop()
+            .expect("pop_viewport without a matching push_viewport");
+        self.position = position;
+        self.clips.truncate(depth);
+    }
+    /// The area that can currently be drawn to, in world coordinates
+    pub fn clip_rect(&self) -> Rect {
+        let (x0, y0, x1, y1) = self.clip_bounds();
+        Rect {
+            x: x0 + self.position.0,
+            y: y0 + self.position.1,
+            w: (x1 - x0) as u16,
+            h: (y1 - y0) as u16,
+        }
+    }
+    /// The current clip in framebuffer coordinates as (x0, y0, x1, y1), with x1 and y1 exclusive
+    #[inline(always)]
+    fn clip_bounds(&self) -> (i32, i32, i32, i32) {
+        match self.clips.last() {
+            Some(r) => (r.x, r.y, r.x + r.w as i32, r.y + r.h as i32),
+            None => (0, 0, self.width as i32, self.height as i32),
+        }
+    }
     // Lots of bounds checks!
     #[inline(always)]
     pub fn draw_at(&mut self, col: Rgba, Vec2i(x, y): Vec2i) {
         let x = x - self.position.0;
         let y = y - self.position.1;
         // The rest is about the same
-        if x < 0 || (self.width as i32) <= x || y < 0 || (self.height as i32) <= y {
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        if x < cx0 || cx1 <= x || y < cy0 || cy1 <= y {
             return;
         }
         assert_eq!(self.depth, 4);
@@ -67,6 +135,12 @@ impl<'fb> Screen<'fb> {
     }
     // If we know the primitives in advance we're in much better shape:
     pub fn clear(&mut self, col: Rgba) {
+        if !self.clips.is_empty() {
+            // Only clear what's inside the clip
+            let r = self.clip_rect();
+            self.rect(r, col);
+            return;
+        }
         let c = [col.0, col.1, col.2, col.3];
         for px in self.framebuffer.chunks_exact_mut(4) {
             px.copy_from_slice(&c);
@@ -80,11 +154,12 @@ impl<'fb> Screen<'fb> {
             y: r.y - self.position.1,
             ..r
         };
-        // And the rest is just the same
-        let x0 = r.x.max(0).min(self.width as i32) as usize;
-        let x1 = (r.x + r.w as i32).max(0).min(self.width as i32) as usize;
-        let y0 = r.y.max(0).min(self.height as i32) as usize;
-        let y1 = (r.y + r.h as i32).max(0).min(self.height as i32) as usize;
+        // And the rest is just the same, but clamped to the clip
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        let x0 = r.x.max(cx0).min(cx1) as usize;
+        let x1 = (r.x + r.w as i32).max(cx0).min(cx1) as usize;
+        let y0 = r.y.max(cy0).min(cy1) as usize;
+        let y1 = (r.y + r.h as i32).max(cy0).min(cy1) as usize;
         let depth = self.depth;
         let pitch = self.width * depth;
         for row in self.framebuffer[(y0 * pitch)..(y1 * pitch)].chunks_exact_mut(pitch) {
@@ -94,47 +169,34 @@ impl<'fb> Screen<'fb> {
         }
     }
 
+    /// Outline of a rect; line_width goes inward from its edges, so it stays inside r
     pub fn empty_rect(&mut self, r: Rect, line_width: usize, col: Rgba) {
-        // Note: line thickness goes inward
         assert!(line_width < r.w as usize);
         assert!(line_width < r.h as usize);
-        let c = [col.0, col.1, col.2, col.3];
-        // Here's the translation
-        let r = Rect {
-            x: r.x - self.position.0,
-            y: r.y - self.position.1,
-            ..r
-        };
-
-        let x0 = r.x.max(0).min(self.width as i32);
-        let x1 = (r.x + r.w as i32).max(0).min(self.width as i32);
-        let y0 = r.y.max(0).min(self.height as i32);
-        let y1 = (r.y + r.h as i32).max(0).min(self.height as i32);
+        // No translation here, line takes care of it (and of clipping).
+        // Lines stop just short of their end, so x1 and y1 are just past the rect.
+        let x0 = r.x;
+        let x1 = r.x + r.w as i32;
+        let y0 = r.y;
+        let y1 = r.y + r.h as i32;
 
         for i in 0..line_width {
             self.line(Vec2i(x0, y0 + i as i32), Vec2i(x1, y0 + i as i32), col);
-            self.line(Vec2i(x1 - i as i32, y0), Vec2i(x1 - i as i32, y1), col);
-            self.line(Vec2i(x0, y1 - i as i32), Vec2i(x1, y1 - i as i32), col);
+            self.line(Vec2i(x1 - 1 - i as i32, y0), Vec2i(x1 - 1 - i as i32, y1), col);
+            self.line(Vec2i(x0, y1 - 1 - i as i32), Vec2i(x1, y1 - 1 - i as i32), col);
             self.line(Vec2i(x0 + i as i32, y0), Vec2i(x0 + i as i32, y1), col);
         }
     }
 
+    /// Like empty_rect, but leaves out the line_width square in each corner
     pub fn empty_rect_no_corners(&mut self, r: Rect, line_width: usize, col: Rgba) {
-        // Note: line thickness goes inward
         assert!(line_width < r.w as usize);
         assert!(line_width < r.h as usize);
-        let c = [col.0, col.1, col.2, col.3];
-        // Here's the translation
-        let r = Rect {
-            x: r.x - self.position.0,
-            y: r.y - self.position.1,
-            ..r
-        };
-
-        let x0 = r.x.max(0).min(self.width as i32);
-        let x1 = (r.x + r.w as i32).max(0).min(self.width as i32);
-        let y0 = r.y.max(0).min(self.height as i32);
-        let y1 = (r.y + r.h as i32).max(0).min(self.height as i32);
+        // No translation here, line takes care of it (and of clipping)
+        let x0 = r.x;
+        let x1 = r.x + r.w as i32;
+        let y0 = r.y;
+        let y1 = r.y + r.h as i32;
 
         for i in 0..line_width {
             self.line(
@@ -143,13 +205,13 @@ impl<'fb> Screen<'fb> {
                 col,
             );
             self.line(
-                Vec2i(x1 - i as i32, y0 + line_width as i32),
-                Vec2i(x1 - i as i32, y1 - line_width as i32),
+                Vec2i(x1 - 1 - i as i32, y0 + line_width as i32),
+                Vec2i(x1 - 1 - i as i32, y1 - line_width as i32),
                 col,
             );
             self.line(
-                Vec2i(x0 + line_width as i32, y1 - i as i32),
-                Vec2i(x1 - line_width as i32, y1 - i as i32),
+                Vec2i(x0 + line_width as i32, y1 - 1 - i as i32),
+                Vec2i(x1 - line_width as i32, y1 - 1 - i as i32),
                 col,
             );
             self.line(
@@ -176,10 +238,9 @@ impl<'fb> Screen<'fb> {
         let dy = -(y1 - y0).abs();
         let sy = if y0 < y1 { 1 } else { -1 };
         let mut err = dx + dy;
-        let width = self.width as i32;
-        let height = self.height as i32;
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
         while x != x1 || y != y1 {
-            if 0 <= x && x < width && 0 <= y && y < height {
+            if cx0 <= x && x < cx1 && cy0 <= y && y < cy1 {
                 // TODO this bounds check could in theory be avoided with
                 // the unsafe get_unchecked, but maybe better not...
                 self.framebuffer[(y as usize * self.width * self.depth + x as usize * self.depth)
@@ -200,58 +261,457 @@ impl<'fb> Screen<'fb> {
         }
     }
 
+    /// Fill a horizontal run of pixels in framebuffer coordinates, x1 exclusive
+    fn span(&mut self, x0: i32, x1: i32, y: i32, col: Rgba) {
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        if y < cy0 || cy1 <= y {
+            return;
+        }
+        let x0 = x0.max(cx0).min(cx1) as usize;
+        let x1 = x1.max(cx0).min(cx1) as usize;
+        if x1 <= x0 {
+            return;
+        }
+        let c = [col.0, col.1, col.2, col.3];
+        let depth = self.depth;
+        let row = y as usize * self.width * depth;
+        for p in self.framebuffer[(row + x0 * depth)..(row + x1 * depth)].chunks_exact_mut(depth) {
+            p.copy_from_slice(&c);
+        }
+    }
+
+    /// Half the width of an ellipse's row `dy` rows away from its centre, if it has one
+    fn ellipse_half_width(rx: i32, ry: i32, dy: i32) -> Option<i32> {
+        if rx < 0 || ry < 0 || dy.abs() > ry {
+            return None;
+        }
+        if ry == 0 {
+            return Some(rx);
+        }
+        // Using r*r + r instead of r*r rounds the shape off nicely at small sizes
+        let rx2 = (rx * rx + rx) as f32;
+        let ry2 = (ry * ry + ry) as f32;
+        let w2 = rx2 * (1.0 - (dy * dy) as f32 / ry2);
+        Some(((w2.max(0.0).sqrt() - 0.001).floor() as i32).max(0))
+    }
+
+    pub fn circle(&mut self, center: Vec2i, radius: i32, col: Rgba) {
+        self.ellipse(center, radius, radius, col);
+    }
+
+    /// A ring line_width thick around the inside edge of the circle `circle` would fill
+    pub fn empty_circle(&mut self, center: Vec2i, radius: i32, line_width: usize, col: Rgba) {
+        self.empty_ellipse(center, radius, radius, line_width, col);
+    }
+
+    pub fn ellipse(&mut self, Vec2i(cx, cy): Vec2i, rx: i32, ry: i32, col: Rgba) {
+        // translate translate
+        let cx = cx - self.position.0;
+        let cy = cy - self.position.1;
+        for dy in -ry..=ry {
+            if let Some(half) = Self::ellipse_half_width(rx, ry, dy) {
+                self.span(cx - half, cx + half + 1, cy + dy, col);
+            }
+        }
+    }
+
+    /// The part of the ellipse `ellipse` would fill that's outside the one line_width smaller
+    pub fn empty_ellipse(&mut self, Vec2i(cx, cy): Vec2i, rx: i32, ry: i32, line_width: usize, col: Rgba) {
+        let cx = cx - self.position.0;
+        let cy = cy - self.position.1;
+        let irx = rx - line_width as i32;
+        let iry = ry - line_width as i32;
+        for dy in -ry..=ry {
+            let outer = match Self::ellipse_half_width(rx, ry, dy) {
+                Some(half) => half,
+                None => continue,
+            };
+            match Self::ellipse_half_width(irx, iry, dy) {
+                // Only the ring between the outer and inner ellipses
+                Some(inner) => {
+                    self.span(cx - outer, cx - inner, cy + dy, col);
+                    self.span(cx + inner + 1, cx + outer + 1, cy + dy, col);
+                }
+                None => self.span(cx - outer, cx + outer + 1, cy + dy, col),
+            }
+        }
+    }
+
+    pub fn triangle(&mut self, a: Vec2i, b: Vec2i, c: Vec2i, col: Rgba) {
+        self.polygon(&[a, b, c], col);
+    }
+
+    /// Outline of a triangle; see empty_polygon
+    pub fn empty_triangle(&mut self, a: Vec2i, b: Vec2i, c: Vec2i, line_width: usize, col: Rgba) {
+        self.empty_polygon(&[a, b, c], line_width, col);
+    }
+
+    /**
+     * Fills a polygon
+     *
+     * Params:
+     *   - points: corners in order, the last one joins back up to the first
+     *   - col: fill color
+     *
+     * Note: Works for concave and self-intersecting polygons too, using the even-odd rule
+     **/
+    pub fn polygon(&mut self, points: &[Vec2i], col: Rgba) {
+        if points.len() < 3 {
+            return;
+        }
+        // translate translate
+        let pts: Vec<(f32, f32)> = points
+            .iter()
+            .map(|p| ((p.0 - self.position.0) as f32, (p.1 - self.position.1) as f32))
+            .collect();
+        self.fill_polygon(&pts, col);
+    }
+
+    /// Scanline fill of a polygon in framebuffer coordinates, using the even-odd rule
+    fn fill_polygon(&mut self, pts: &[(f32, f32)], col: Rgba) {
+        let y_min = pts.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let y_max = pts.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        let (_, cy0, _, cy1) = self.clip_bounds();
+        let y0 = (y_min.floor() as i32).max(cy0);
+        let y1 = (y_max.ceil() as i32).min(cy1);
+        let mut crossings: Vec<f32> = Vec::with_capacity(pts.len());
+        for y in y0..y1 {
+            // Scan along the pixel centres
+            let yc = y as f32 + 0.5;
+            polygon_crossings(pts, yc, &mut crossings);
+            for pair in crossings.chunks_exact(2) {
+                // Fill pixels whose centres are inside
+                let x0 = (pair[0] - 0.5).ceil() as i32;
+                let x1 = (pair[1] - 0.5).ceil() as i32;
+                self.span(x0, x1, y, col);
+            }
+        }
+    }
+
+    /// Fill the pixels fill_polygon would that are less than `width` from one of the polygon's edges
+    fn fill_polygon_border(&mut self, pts: &[(f32, f32)], width: f32, col: Rgba) {
+        let y_min = pts.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let y_max = pts.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        let y0 = (y_min.floor() as i32).max(cy0);
+        let y1 = (y_max.ceil() as i32).min(cy1);
+        let mut crossings: Vec<f32> = Vec::with_capacity(pts.len());
+        for y in y0..y1 {
+            let yc = y as f32 + 0.5;
+            polygon_crossings(pts, yc, &mut crossings);
+            for pair in crossings.chunks_exact(2) {
+                let x0 = ((pair[0] - 0.5).ceil() as i32).max(cx0);
+                let x1 = ((pair[1] - 0.5).ceil() as i32).min(cx1);
+                // Fill runs of pixels near an edge
+                let mut run = None;
+                for x in x0..x1 {
+                    let near = edge_distance(pts, (x as f32 + 0.5, yc)) < width;
+                    match (near, run) {
+                        (true, None) => run = Some(x),
+                        (false, Some(start)) => {
+                            self.span(start, x, y, col);
+                            run = None;
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(start) = run {
+                    self.span(start, x1, y, col);
+                }
+            }
+        }
+    }
+
+    /// Fill a disc in framebuffer coordinates, for round caps and joins
+    fn fill_disc(&mut self, (cx, cy): (f32, f32), r: f32, col: Rgba) {
+        let (_, clip_y0, _, clip_y1) = self.clip_bounds();
+        let y0 = ((cy - r).floor() as i32).max(clip_y0);
+        let y1 = ((cy + r).ceil() as i32).min(clip_y1);
+        for y in y0..y1 {
+            let dy = y as f32 + 0.5 - cy;
+            if dy.abs() > r {
+                continue;
+            }
+            let half = (r * r - dy * dy).sqrt();
+            let x0 = (cx - half - 0.5).ceil() as i32;
+            let x1 = (cx + half - 0.5).ceil() as i32;
+            self.span(x0, x1, y, col);
+        }
+    }
+
+    /**
+     * Draws the outline of a polygon
+     *
+     * Params:
+     *   - points: corners in order, the last one joins back up to the first
+     *   - line_width: how thick the outline is, measured inward from the edges
+     *   - col: outline color
+     *
+     * Note: The outline covers exactly the pixels `polygon` fills that are within
+     * line_width of an edge, so it never sticks out past the filled shape
+     **/
+    pub fn empty_polygon(&mut self, points: &[Vec2i], line_width: usize, col: Rgba) {
+        if points.len() < 3 || line_width == 0 {
+            return;
+        }
+        // translate translate
+        let pts: Vec<(f32, f32)> = points
+            .iter()
+            .map(|p| ((p.0 - self.position.0) as f32, (p.1 - self.position.1) as f32))
+            .collect();
+        self.fill_polygon_border(&pts, line_width as f32, col);
+    }
+
+    /**
+     * Draws a line more than one pixel wide
+     *
+     * Params:
+     *   - a, b: ends of the line
+     *   - stroke: width and cap style
+     *   - col: color of the line
+     **/
+    pub fn thick_line(&mut self, a: Vec2i, b: Vec2i, stroke: Stroke, col: Rgba) {
+        self.polyline(&[a, b], false, stroke, col);
+    }
+
+    /**
+     * Draws connected line segments
+     *
+     * Params:
+     *   - points: the points to join up in order
+     *   - closed: also join the last point back to the first
+     *   - stroke: width, cap and join style
+     *   - col: color of the line
+     *
+     * Note: Strokes are centred on the pixels the points name, and one pixel
+     * strokes are just plain lines
+     **/
+    pub fn polyline(&mut self, points: &[Vec2i], closed: bool, stroke: Stroke, col: Rgba) {
+        if stroke.width <= 1 {
+            let n = points.len();
+            let seg_count = if closed { n } else { n.saturating_sub(1) };
+            for i in 0..seg_count {
+                self.line(points[i], points[(i + 1) % n], col);
+            }
+            return;
+        }
+        // translate translate, and drop repeated points since they have no direction
+        let mut pts: Vec<(f32, f32)> = Vec::with_capacity(points.len());
+        for p in points {
+            let p = (
+                (p.0 - self.position.0) as f32 + 0.5,
+                (p.1 - self.position.1) as f32 + 0.5,
+            );
+            if pts.last() != Some(&p) {
+                pts.push(p);
+            }
+        }
+        if closed && pts.len() > 1 && pts.first() == pts.last() {
+            pts.pop();
+        }
+        if pts.len() < 2 {
+            if let Some(&p) = pts.first() {
+                self.fill_disc(p, stroke.width as f32 / 2.0, col);
+            }
+            return;
+        }
+        let seg_count = if closed { pts.len() } else { pts.len() - 1 };
+        let half = stroke.width as f32 / 2.0;
+        // Unit direction and normal of a segment
+        let dir = |a: (f32, f32), b: (f32, f32)| {
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let len = (dx * dx + dy * dy).sqrt();
+            (dx / len, dy / len)
+        };
+        for i in 0..seg_count {
+            let mut a = pts[i];
+            let mut b = pts[(i + 1) % pts.len()];
+            let (dx, dy) = dir(a, b);
+            if !closed && stroke.cap == LineCap::Square {
+                // Square caps just stick out by half the width
+                if i == 0 {
+                    a = (a.0 - dx * half, a.1 - dy * half);
+                }
+                if i == seg_count - 1 {
+                    b = (b.0 + dx * half, b.1 + dy * half);
+                }
+            }
+            let (nx, ny) = (-dy * half, dx * half);
+            self.fill_polygon(
+                &[
+                    (a.0 + nx, a.1 + ny),
+                    (b.0 + nx, b.1 + ny),
+                    (b.0 - nx, b.1 - ny),
+                    (a.0 - nx, a.1 - ny),
+                ],
+                col,
+            );
+        }
+        if !closed && stroke.cap == LineCap::Round {
+            self.fill_disc(pts[0], half, col);
+            self.fill_disc(pts[pts.len() - 1], half, col);
+        }
+        // Joins go at every point with a segment on both sides
+        let joins = if closed { 0..pts.len() } else { 1..(pts.len() - 1) };
+        for i in joins {
+            let v = pts[i];
+            let prev = pts[(i + pts.len() - 1) % pts.len()];
+            let next = pts[(i + 1) % pts.len()];
+            let d0 = dir(prev, v);
+            let d1 = dir(v, next);
+            let n0 = (-d0.1, d0.0);
+            let n1 = (-d1.1, d1.0);
+            // The gap to fill is on the side facing away from the turn
+            let side = if n0.0 * d1.0 + n0.1 * d1.1 > 0.0 { -1.0 } else { 1.0 };
+            let p0 = (v.0 + side * n0.0 * half, v.1 + side * n0.1 * half);
+            let p1 = (v.0 + side * n1.0 * half, v.1 + side * n1.1 * half);
+            match stroke.join {
+                LineJoin::Round => self.fill_disc(v, half, col),
+                LineJoin::Bevel => self.fill_polygon(&[v, p0, p1], col),
+                LineJoin::Miter => {
+                    let m = (n0.0 + n1.0, n0.1 + n1.1);
+                    let m_len = (m.0 * m.0 + m.1 * m.1).sqrt();
+                    // How far out the corner is, compared to half the width
+                    let ratio = if m_len > 0.0 { 2.0 / m_len } else { f32::MAX };
+                    if ratio > MITER_LIMIT {
+                        self.fill_polygon(&[v, p0, p1], col);
+                    } else {
+                        let tip = (
+                            v.0 + side * m.0 / m_len * half * ratio,
+                            v.1 + side * m.1 / m_len * half * ratio,
+                        );
+                        self.fill_polygon(&[v, p0, tip, p1], col);
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+     * Draws an anti-aliased one pixel line (Xiaolin Wu's algorithm)
+     *
+     * Params:
+     *   - a, b: ends of the line
+     *   - col: color of the line, blended over what's already there
+     **/
+    pub fn line_aa(&mut self, a: Vec2i, b: Vec2i, col: Rgba) {
+        // translate translate
+        let (mut x0, mut y0) = ((a.0 - self.position.0) as f32, (a.1 - self.position.1) as f32);
+        let (mut x1, mut y1) = ((b.0 - self.position.0) as f32, (b.1 - self.position.1) as f32);
+        // Walk along whichever axis the line is longer in
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+        let mut intery = y0;
+        for x in (x0 as i32)..=(x1 as i32) {
+            // Split the coverage between the two pixels the line passes between
+            let y = intery.floor();
+            let f = intery - y;
+            let y = y as i32;
+            if steep {
+                self.blend_at(y, x, col, 1.0 - f);
+                self.blend_at(y + 1, x, col, f);
+            } else {
+                self.blend_at(x, y, col, 1.0 - f);
+                self.blend_at(x, y + 1, col, f);
+            }
+            intery += gradient;
+        }
+    }
+
+    /// Blend a color over one pixel in framebuffer coordinates, scaled by coverage
+    fn blend_at(&mut self, x: i32, y: i32, col: Rgba, coverage: f32) {
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        if coverage <= 0.0 || x < cx0 || cx1 <= x || y < cy0 || cy1 <= y {
+            return;
+        }
+        let a = col.3 as f32 / 255.0 * coverage.min(1.0);
+        let src = [
+            (col.0 as f32 * a).round() as u8,
+            (col.1 as f32 * a).round() as u8,
+            (col.2 as f32 * a).round() as u8,
+            (255.0 * a).round() as u8,
+        ];
+        let idx = y as usize * self.width * self.depth + x as usize * self.depth;
+        Blend::default().composite(&mut self.framebuffer[idx..(idx + self.depth)], &src);
+    }
+
     /**
      * Draws text on the screen
      *
      * Params:
-     *   - rasterized: a HashMap of already rasterized glyphs
-     *   - font: the font to use
+     *   - fonts: the fonts the layout uses, and their glyph caches
      *   - layout: layout to draw
-     *   - col: color of text
+     *   - col: color of text, blended by each glyph's coverage
      *
-     * Note: Cuts off text that does not fit in the screen
+     * Note: Cuts off text at the edges of the screen or the current clip
      **/
     pub fn draw_text(
         &mut self,
-        rasterized: &mut HashMap<u64, (Metrics, Vec<u8>)>,
-        font: &Font,
+        fonts: &mut Fonts,
         layout: &mut Layout,
         col: Rgba,
     ) {
-        let mut h = DefaultHasher::new();
-        // hash the glyph for the key
         for glyph in layout.glyphs() {
-            glyph.key.hash(&mut h);
-            let (_metrics, bitmap) = rasterized
-                .entry(h.finish())
-                .or_insert_with(|| font.rasterize(glyph.key.c, glyph.key.px));
-
-            let col = [col.0, col.1, col.2, col.3];
-            let depth = self.depth;
-            let pitch = self.width * self.depth;
-            let x0 = (glyph.x as i32 - self.position.0) as usize;
-            let y0 = (glyph.y as i32 - self.position.1) as usize;
-            let x1 = (glyph.x as i32 - self.position.0) as usize + glyph.width;
-            let y1 = (glyph.y as i32 - self.position.1) as usize + glyph.height;
-            if x1 < self.width && y1 < self.height {
-                for (j, row) in self.framebuffer[(y0 * pitch)..(y1 * pitch)]
-                    .chunks_exact_mut(pitch)
-                    .enumerate()
-                {
-                    for (i, p) in row[(x0 * depth)..(x1 * depth)]
-                        .chunks_exact_mut(depth)
-                        .enumerate()
-                    {
-                        if bitmap[j * glyph.width + i] != 0 {
-                            p.copy_from_slice(&col);
-                        }
+            let (_metrics, bitmap) = fonts.glyph(glyph.key);
+
+            // Translate, flooring so glyphs left of or above the camera don't shift over
+            let gx = glyph.x.floor() as i32 - self.position.0;
+            let gy = glyph.y.floor() as i32 - self.position.1;
+            let gw = glyph.width as i32;
+            let gh = glyph.height as i32;
+            if bitmap.len() < glyph.width * glyph.height {
+                continue;
+            }
+            // Clip the glyph's box so partly visible glyphs still get drawn
+            let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+            let x0 = gx.max(cx0);
+            let x1 = (gx + gw).min(cx1);
+            let y0 = gy.max(cy0);
+            let y1 = (gy + gh).min(cy1);
+            for y in y0..y1 {
+                let row = ((y - gy) * gw) as usize;
+                for x in x0..x1 {
+                    // Coverage is how much of the pixel the glyph covers
+                    let coverage = bitmap[row + (x - gx) as usize];
+                    if coverage != 0 {
+                        self.blend_at(x, y, col, coverage as f32 / 255.0);
                     }
                 }
             }
         }
     }
 
-    pub fn bitblt(&mut self, src: &Texture, from: Rect, Vec2i(to_x, to_y): Vec2i, flipped: bool) {
+    pub fn bitblt(&mut self, src: &Texture, from: Rect, to: Vec2i, flipped: bool) {
+        self.bitblt_blend(src, from, to, flipped, Blend::default());
+    }
+
+    /**
+     * Draws a frame of a texture, compositing it with the given blend settings
+     *
+     * Params:
+     *   - src: texture to draw from
+     *   - from: frame of the texture to draw
+     *   - to: world position of the frame's top left corner
+     *   - flipped: mirror the frame horizontally
+     *   - blend: blend mode, tint and opacity
+     **/
+    pub fn bitblt_blend(
+        &mut self,
+        src: &Texture,
+        from: Rect,
+        Vec2i(to_x, to_y): Vec2i,
+        flipped: bool,
+        blend: Blend,
+    ) {
         let (tw, th) = src.size();
         assert!(0 <= from.x);
         assert!(from.x < tw as i32);
@@ -260,11 +720,8 @@ impl<'fb> Screen<'fb> {
         let to_x = to_x - self.position.0;
         let to_y = to_y - self.position.1;
         assert!(src.valid_frame(from));
-        if (to_x + from.w as i32) < 0
-            || (self.width as i32) <= to_x
-            || (to_y + from.h as i32) < 0
-            || (self.height as i32) <= to_y
-        {
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        if (to_x + from.w as i32) < cx0 || cx1 <= to_x || (to_y + from.h as i32) < cy0 || cy1 <= to_y {
             return;
         }
         let depth = self.depth;
@@ -274,10 +731,10 @@ impl<'fb> Screen<'fb> {
         // All this rigmarole is just to avoid bounds checks on each pixel of the blit.
         // We want to calculate which row/col of the src image to start at and which to end at.
         // This way there's no need to even check for out of bounds draws.
-        let y_skip = to_y.max(0) - to_y;
-        let x_skip = to_x.max(0) - to_x;
-        let y_count = (to_y + from.h as i32).min(self.height as i32) - to_y;
-        let x_count = (to_x + from.w as i32).min(self.width as i32) - to_x;
+        let y_skip = to_y.max(cy0) - to_y;
+        let x_skip = to_x.max(cx0) - to_x;
+        let y_count = (to_y + from.h as i32).min(cy1) - to_y;
+        let x_count = (to_x + from.w as i32).min(cx1) - to_x;
         // The code above is gnarly so these are just for safety:
         debug_assert!(0 <= x_skip);
         debug_assert!(0 <= y_skip);
@@ -289,8 +746,14 @@ impl<'fb> Screen<'fb> {
         debug_assert!(0 <= to_y + y_skip);
         debug_assert!(0 <= from.x + x_skip);
         debug_assert!(0 <= from.y + y_skip);
-        debug_assert!(to_x + x_count <= self.width as i32);
-        debug_assert!(to_y + y_count <= self.height as i32);
+        debug_assert!(to_x + x_count <= cx1);
+        debug_assert!(to_y + y_count <= cy1);
+        // When flipped, the visible columns come from the other end of the frame
+        let (col0, col1) = if flipped {
+            (from.w as i32 - x_count, from.w as i32 - x_skip)
+        } else {
+            (x_skip, x_count)
+        };
         // OK, let's do some copying now
         let src_buf = src.buffer();
         for (row_a, row_b) in src_buf
@@ -302,39 +765,622 @@ impl<'fb> Screen<'fb> {
                     .chunks_exact_mut(dst_pitch),
             )
         {
+            let to_cols = row_b
+                [(depth * (to_x + x_skip) as usize)..(depth * (to_x + x_count) as usize)]
+                .chunks_exact_mut(depth);
+            let from_cols = row_a
+                [(depth * (from.x + col0) as usize)..(depth * (from.x + col1) as usize)]
+                .chunks_exact(depth);
             if flipped {
-                let to_cols = row_b
-                    [(depth * (to_x + x_skip) as usize)..(depth * (to_x + x_count) as usize)]
-                    .chunks_exact_mut(depth).rev();
-                let from_cols = row_a
-                    [(depth * (from.x + x_skip) as usize)..(depth * (from.x + x_count) as usize)]
-                    .chunks_exact(depth);
-                // Composite over, assume premultiplied rgba8888
-                for (to, from) in to_cols.zip(from_cols) {
-                    let ta = to[3] as f32 / 255.0;
-                    let fa = from[3] as f32 / 255.0;
-                    for i in 0..3 {
-                        to[i] = from[i].saturating_add((to[i] as f32 * (1.0 - fa)).round() as u8);
-                    }
-                    to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
+                for (to, from) in to_cols.zip(from_cols.rev()) {
+                    blend.composite(to, from);
                 }
             } else {
-                let to_cols = row_b
-                    [(depth * (to_x + x_skip) as usize)..(depth * (to_x + x_count) as usize)]
-                    .chunks_exact_mut(depth);
-                let from_cols = row_a
-                    [(depth * (from.x + x_skip) as usize)..(depth * (from.x + x_count) as usize)]
-                    .chunks_exact(depth);
-                // Composite over, assume premultiplied rgba8888
                 for (to, from) in to_cols.zip(from_cols) {
-                    let ta = to[3] as f32 / 255.0;
-                    let fa = from[3] as f32 / 255.0;
-                    for i in 0..3 {
-                        to[i] = from[i].saturating_add((to[i] as f32 * (1.0 - fa)).round() as u8);
+                    blend.composite(to, from);
+                }
+            }
+        }
+    }
+
+    /**
+     * Draws a frame of a texture flipped and/or turned by quarter turns
+     *
+     * Params:
+     *   - src: texture to draw from
+     *   - from: frame of the texture to draw
+     *   - to: world position of the transformed frame's top left corner
+     *   - transform: flips and rotation to apply
+     *   - blend: blend mode, tint and opacity
+     *
+     * Note: A quarter turn swaps the width and height of what gets drawn
+     **/
+    pub fn bitblt_transformed(
+        &mut self,
+        src: &Texture,
+        from: Rect,
+        Vec2i(to_x, to_y): Vec2i,
+        transform: Transform,
+        blend: Blend,
+    ) {
+        assert!(src.valid_frame(from));
+        assert_eq!(self.depth, src.depth());
+        let (dw, dh) = transform.size(from.w as i32, from.h as i32);
+        let to_x = to_x - self.position.0;
+        let to_y = to_y - self.position.1;
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        if (to_x + dw) < cx0 || cx1 <= to_x || (to_y + dh) < cy0 || cy1 <= to_y {
+            return;
+        }
+        // Same clipping as bitblt, but in the transformed frame
+        let y_skip = to_y.max(cy0) - to_y;
+        let x_skip = to_x.max(cx0) - to_x;
+        let y_count = (to_y + dh).min(cy1) - to_y;
+        let x_count = (to_x + dw).min(cx1) - to_x;
+        let depth = self.depth;
+        let src_pitch = src.pitch();
+        let dst_pitch = self.width * depth;
+        let src_buf = src.buffer();
+        for y in y_skip..y_count {
+            let row = dst_pitch * (to_y + y) as usize;
+            for x in x_skip..x_count {
+                let Vec2i(sx, sy) = transform.source(Vec2i(x, y), from.w as i32, from.h as i32);
+                let s = src_pitch * (from.y + sy) as usize + depth * (from.x + sx) as usize;
+                let d = row + depth * (to_x + x) as usize;
+                blend.composite(
+                    &mut self.framebuffer[d..(d + depth)],
+                    &src_buf[s..(s + depth)],
+                );
+            }
+        }
+    }
+
+    /**
+     * Draws a frame of a texture rotated by any angle around a pivot
+     *
+     * Params:
+     *   - src: texture to draw from
+     *   - from: frame of the texture to draw
+     *   - to: world position of the unrotated frame's top left corner
+     *   - pivot: point to rotate around, relative to the frame's top left corner
+     *   - angle: clockwise rotation in radians
+     *   - blend: blend mode, tint and opacity
+     *
+     * Note: Uses nearest-neighbour sampling so pixel art stays crisp
+     **/
+    pub fn bitblt_rotated(
+        &mut self,
+        src: &Texture,
+        from: Rect,
+        Vec2i(to_x, to_y): Vec2i,
+        Vec2i(pivot_x, pivot_y): Vec2i,
+        angle: f32,
+        blend: Blend,
+    ) {
+        assert!(src.valid_frame(from));
+        assert_eq!(self.depth, src.depth());
+        let (sin, cos) = angle.sin_cos();
+        // Where the pivot ends up on the framebuffer
+        let px = (to_x + pivot_x - self.position.0) as f32;
+        let py = (to_y + pivot_y - self.position.1) as f32;
+        let (fw, fh) = (from.w as f32, from.h as f32);
+        // Rotate the frame's corners to find the box we need to cover
+        let mut x_min = f32::MAX;
+        let mut x_max = f32::MIN;
+        let mut y_min = f32::MAX;
+        let mut y_max = f32::MIN;
+        for &(cx, cy) in [(0.0, 0.0), (fw, 0.0), (0.0, fh), (fw, fh)].iter() {
+            let vx = cx - pivot_x as f32;
+            let vy = cy - pivot_y as f32;
+            let rx = px + vx * cos - vy * sin;
+            let ry = py + vx * sin + vy * cos;
+            x_min = x_min.min(rx);
+            x_max = x_max.max(rx);
+            y_min = y_min.min(ry);
+            y_max = y_max.max(ry);
+        }
+        // And clip that box
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        let x0 = (x_min.floor() as i32).max(cx0);
+        let x1 = (x_max.ceil() as i32).min(cx1);
+        let y0 = (y_min.floor() as i32).max(cy0);
+        let y1 = (y_max.ceil() as i32).min(cy1);
+        let depth = self.depth;
+        let src_pitch = src.pitch();
+        let dst_pitch = self.width * depth;
+        let src_buf = src.buffer();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Sample at the pixel centre, rotated back into the frame
+                let vx = x as f32 + 0.5 - px;
+                let vy = y as f32 + 0.5 - py;
+                let sx = (vx * cos + vy * sin + pivot_x as f32).floor() as i32;
+                let sy = (-vx * sin + vy * cos + pivot_y as f32).floor() as i32;
+                if sx < 0 || from.w as i32 <= sx || sy < 0 || from.h as i32 <= sy {
+                    continue;
+                }
+                let s = src_pitch * (from.y + sy) as usize + depth * (from.x + sx) as usize;
+                let d = dst_pitch * y as usize + depth * x as usize;
+                blend.composite(
+                    &mut self.framebuffer[d..(d + depth)],
+                    &src_buf[s..(s + depth)],
+                );
+            }
+        }
+    }
+
+    /**
+     * Draws a frame of a texture stretched to fill a rect
+     *
+     * Params:
+     *   - src: texture to draw from
+     *   - from: frame of the texture to draw
+     *   - to: world rect to fill, any size
+     *   - filter: how to sample the frame
+     *   - blend: blend mode, tint and opacity
+     **/
+    pub fn bitblt_scaled(&mut self, src: &Texture, from: Rect, to: Rect, filter: Filter, blend: Blend) {
+        assert!(src.valid_frame(from));
+        assert_eq!(self.depth, src.depth());
+        if from.w == 0 || from.h == 0 || to.w == 0 || to.h == 0 {
+            return;
+        }
+        let to_x = to.x - self.position.0;
+        let to_y = to.y - self.position.1;
+        // Clip the destination rect, like bitblt does
+        let (cx0, cy0, cx1, cy1) = self.clip_bounds();
+        let x0 = to_x.max(cx0);
+        let x1 = (to_x + to.w as i32).min(cx1);
+        let y0 = to_y.max(cy0);
+        let y1 = (to_y + to.h as i32).min(cy1);
+        if x1 <= x0 || y1 <= y0 {
+            return;
+        }
+        let depth = self.depth;
+        let src_pitch = src.pitch();
+        let dst_pitch = self.width * depth;
+        let src_buf = src.buffer();
+        // How many source pixels per destination pixel
+        let sx_step = from.w as f32 / to.w as f32;
+        let sy_step = from.h as f32 / to.h as f32;
+        let texel = |x: i32, y: i32| {
+            let s = src_pitch * (from.y + y) as usize + depth * (from.x + x) as usize;
+            &src_buf[s..(s + depth)]
+        };
+        for y in y0..y1 {
+            // Sample at the pixel centre
+            let v = (y - to_y) as f32 + 0.5;
+            for x in x0..x1 {
+                let u = (x - to_x) as f32 + 0.5;
+                let d = dst_pitch * y as usize + depth * x as usize;
+                match filter {
+                    Filter::Nearest => {
+                        let sx = ((u * sx_step) as i32).min(from.w as i32 - 1);
+                        let sy = ((v * sy_step) as i32).min(from.h as i32 - 1);
+                        blend.composite(&mut self.framebuffer[d..(d + depth)], texel(sx, sy));
+                    }
+                    Filter::Bilinear => {
+                        // Texel centres are at +0.5, and we don't sample outside the frame
+                        let fx = (u * sx_step - 0.5).max(0.0).min(from.w as f32 - 1.0);
+                        let fy = (v * sy_step - 0.5).max(0.0).min(from.h as f32 - 1.0);
+                        let sx = fx as i32;
+                        let sy = fy as i32;
+                        let sx1 = (sx + 1).min(from.w as i32 - 1);
+                        let sy1 = (sy + 1).min(from.h as i32 - 1);
+                        let tx = fx - sx as f32;
+                        let ty = fy - sy as f32;
+                        let (a, b, c, e) = (texel(sx, sy), texel(sx1, sy), texel(sx, sy1), texel(sx1, sy1));
+                        // Texels are premultiplied so they can be mixed directly
+                        let mut px = [0; 4];
+                        for i in 0..4 {
+                            let top = a[i] as f32 * (1.0 - tx) + b[i] as f32 * tx;
+                            let bot = c[i] as f32 * (1.0 - tx) + e[i] as f32 * tx;
+                            px[i] = (top * (1.0 - ty) + bot * ty).round() as u8;
+                        }
+                        blend.composite(&mut self.framebuffer[d..(d + depth)], &px);
                     }
-                    to[3] = ((fa + ta * (1.0 - fa)) * 255.0).round() as u8;
                 }
             }
         }
     }
 }
+
+/// Where a polygon's edges cross the horizontal line at `y`, sorted left to right
+fn polygon_crossings(pts: &[(f32, f32)], y: f32, into: &mut Vec<f32>) {
+    into.clear();
+    for (i, &(ax, ay)) in pts.iter().enumerate() {
+        let (bx, by) = pts[(i + 1) % pts.len()];
+        if (ay <= y && y < by) || (by <= y && y < ay) {
+            into.push(ax + (y - ay) * (bx - ax) / (by - ay));
+        }
+    }
+    into.sort_by(|a, b| a.partial_cmp(b).unwrap());
+}
+
+/// How far a point is from the nearest edge of a polygon
+fn edge_distance(pts: &[(f32, f32)], (px, py): (f32, f32)) -> f32 {
+    let mut nearest = f32::MAX;
+    for (i, &(ax, ay)) in pts.iter().enumerate() {
+        let (bx, by) = pts[(i + 1) % pts.len()];
+        let (dx, dy) = (bx - ax, by - ay);
+        let len2 = dx * dx + dy * dy;
+        // How far along the edge the closest point is, kept on the edge
+        let t = if len2 > 0.0 {
+            (((px - ax) * dx + (py - ay) * dy) / len2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (ex, ey) = (ax + t * dx - px, ay + t * dy - py);
+        nearest = nearest.min((ex * ex + ey * ey).sqrt());
+    }
+    nearest
+}
+
+/// How `bitblt_scaled` samples the source frame
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Filter {
+    /// Blocky, best for whole-number pixel art zooms
+    Nearest,
+    /// Smooth, blends the four closest texels
+    Bilinear,
+}
+
+/// A region of the framebuffer with its own camera
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Viewport {
+    /// Where on the framebuffer to draw
+    pub region: Rect,
+    /// The world position shown at the region's top left corner
+    pub camera: Vec2i,
+}
+impl Viewport {
+    pub fn new(region: Rect, camera: Vec2i) -> Self {
+        Self { region, camera }
+    }
+    /// The world rect this viewport can see
+    pub fn visible(&self) -> Rect {
+        Rect {
+            x: self.camera.0,
+            y: self.camera.1,
+            ..self.region
+        }
+    }
+}
+
+/// Quarter turns a frame can be rotated by
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Rotation {
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+/// Flips and quarter turns for `bitblt_transformed`.
+/// Flips happen first, then the rotation.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Transform {
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub rotation: Rotation,
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            flip_h: false,
+            flip_v: false,
+            rotation: Rotation::None,
+        }
+    }
+}
+impl Transform {
+    pub fn flip_h() -> Self {
+        Self {
+            flip_h: true,
+            ..Self::default()
+        }
+    }
+    pub fn flip_v() -> Self {
+        Self {
+            flip_v: true,
+            ..Self::default()
+        }
+    }
+    pub fn rotate(rotation: Rotation) -> Self {
+        Self {
+            rotation,
+            ..Self::default()
+        }
+    }
+    /// Size of a w by h frame after this transform
+    pub fn size(&self, w: i32, h: i32) -> (i32, i32) {
+        match self.rotation {
+            Rotation::Cw90 | Rotation::Cw270 => (h, w),
+            Rotation::None | Rotation::Cw180 => (w, h),
+        }
+    }
+    /// Which pixel of a w by h frame ends up at (x, y) after this transform
+    #[inline(always)]
+    fn source(&self, Vec2i(x, y): Vec2i, w: i32, h: i32) -> Vec2i {
+        // Undo the rotation...
+        let (x, y) = match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Cw90 => (y, h - 1 - x),
+            Rotation::Cw180 => (w - 1 - x, h - 1 - y),
+            Rotation::Cw270 => (w - 1 - y, x),
+        };
+        // ...then the flips
+        Vec2i(
+            if self.flip_h { w - 1 - x } else { x },
+            if self.flip_v { h - 1 - y } else { y },
+        )
+    }
+}
+
+/// How far a miter join may stick out, in half line widths, before it's beveled instead
+const MITER_LIMIT: f32 = 4.0;
+
+/// What the open ends of a thick line look like
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LineCap {
+    /// Stop right at the end point
+    Butt,
+    /// Stick out past the end point by half the width
+    Square,
+    /// A half circle around the end point
+    Round,
+}
+
+/// What the corners between segments of a thick line look like
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LineJoin {
+    /// A sharp corner, beveled if it gets too long
+    Miter,
+    /// The corner cut off flat
+    Bevel,
+    /// A rounded corner
+    Round,
+}
+
+/// How to draw a thick line
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Stroke {
+    pub width: usize,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+impl Stroke {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+        }
+    }
+    pub fn with_cap(self, cap: LineCap) -> Self {
+        Self { cap, ..self }
+    }
+    pub fn with_join(self, join: LineJoin) -> Self {
+        Self { join, ..self }
+    }
+}
+
+/// How a source pixel gets combined with what's already on screen
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum BlendMode {
+    /// Regular alpha compositing
+    Over,
+    /// Add the source onto the destination, good for glints and glows
+    Additive,
+    /// Darken the destination by the source
+    Multiply,
+    /// Lighten the destination by the inverse of the source
+    Screen,
+    /// Replace the destination with the source, alpha and all
+    Copy,
+}
+
+/// Which color values blending does its math on
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColorSpace {
+    /// The sRGB values as stored; fast, but soft edges come out a little dark
+    Srgb,
+    /// Linear light, converting to and from sRGB around every blend; slower, but
+    /// semi-transparent edges and glows blend the way light actually adds up
+    Linear,
+}
+impl ColorSpace {
+    /// Turn a premultiplied rgba8888 pixel into premultiplied 0-1 values in this space
+    #[inline(always)]
+    fn decode(self, px: &[u8]) -> [f32; 4] {
+        let a = px[3] as f32 / 255.0;
+        let mut out = [0.0, 0.0, 0.0, a];
+        for i in 0..3 {
+            let c = px[i] as f32 / 255.0;
+            out[i] = match self {
+                ColorSpace::Srgb => c,
+                // Undo the premultiply first, since sRGB's curve applies to the straight color
+                ColorSpace::Linear if a > 0.0 => srgb_to_linear((c / a).min(1.0)) * a,
+                ColorSpace::Linear => srgb_to_linear(c),
+            };
+        }
+        out
+    }
+    /// The inverse of `decode`, rounding to the nearest value
+    #[inline(always)]
+    fn encode(self, px: [f32; 4], to: &mut [u8]) {
+        let a = px[3].clamp(0.0, 1.0);
+        for i in 0..3 {
+            let c = px[i].clamp(0.0, 1.0);
+            let c = match self {
+                ColorSpace::Srgb => c,
+                ColorSpace::Linear if a > 0.0 => linear_to_srgb((c / a).min(1.0)) * a,
+                ColorSpace::Linear => linear_to_srgb(c),
+            };
+            to[i] = (c * 255.0).round() as u8;
+        }
+        to[3] = (a * 255.0).round() as u8;
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Blend settings for a blit
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Blend {
+    pub mode: BlendMode,
+    /// Multiplied into every source pixel (not premultiplied)
+    pub tint: Rgba,
+    /// Scales the source's alpha, 0.0 to 1.0
+    pub opacity: f32,
+    pub space: ColorSpace,
+}
+impl Default for Blend {
+    fn default() -> Self {
+        Self {
+            mode: BlendMode::Over,
+            tint: Rgba(255, 255, 255, 255),
+            opacity: 1.0,
+            space: ColorSpace::Srgb,
+        }
+    }
+}
+impl Blend {
+    pub fn new(mode: BlendMode) -> Self {
+        Self {
+            mode,
+            ..Self::default()
+        }
+    }
+    pub fn with_tint(self, tint: Rgba) -> Self {
+        Self { tint, ..self }
+    }
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        Self {
+            opacity: opacity.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+    pub fn with_space(self, space: ColorSpace) -> Self {
+        Self { space, ..self }
+    }
+    /// Apply the tint and opacity to a premultiplied source pixel
+    #[inline(always)]
+    fn modulate(&self, from: &[u8]) -> [u8; 4] {
+        if self.tint == Rgba(255, 255, 255, 255) && self.opacity >= 1.0 {
+            return [from[0], from[1], from[2], from[3]];
+        }
+        // Tint alpha and opacity scale the whole premultiplied pixel
+        let a = (self.tint.3 as f32 / 255.0) * self.opacity;
+        let tint = [self.tint.0, self.tint.1, self.tint.2];
+        let mut out = [0; 4];
+        for i in 0..3 {
+            out[i] = (from[i] as f32 * (tint[i] as f32 / 255.0) * a).round() as u8;
+        }
+        out[3] = (from[3] as f32 * a).round() as u8;
+        out
+    }
+    /// Composite one premultiplied rgba8888 pixel onto another
+    #[inline(always)]
+    pub fn composite(&self, to: &mut [u8], from: &[u8]) {
+        let from = self.modulate(from);
+        match (self.mode, self.space) {
+            // Integer fast paths for the common cases, giving exactly what composite_float would
+            (BlendMode::Over, ColorSpace::Srgb) => {
+                let inv = 255 - from[3];
+                for i in 0..4 {
+                    to[i] = from[i].saturating_add(mul(to[i], inv));
+                }
+            }
+            (BlendMode::Additive, ColorSpace::Srgb) => {
+                for i in 0..4 {
+                    to[i] = to[i].saturating_add(from[i]);
+                }
+            }
+            (BlendMode::Copy, _) => to.copy_from_slice(&from),
+            _ => self.composite_float(to, &from),
+        }
+    }
+    /// Composite in floating point in the blend's color space
+    fn composite_float(&self, to: &mut [u8], from: &[u8]) {
+        let s = self.space.decode(from);
+        let d = self.space.decode(to);
+        let (sa, da) = (s[3], d[3]);
+        let over_alpha = sa + da * (1.0 - sa);
+        let mut out = [0.0; 4];
+        match self.mode {
+            BlendMode::Over => {
+                for i in 0..3 {
+                    out[i] = s[i] + d[i] * (1.0 - sa);
+                }
+                out[3] = over_alpha;
+            }
+            BlendMode::Additive => {
+                for i in 0..4 {
+                    out[i] = (s[i] + d[i]).min(1.0);
+                }
+            }
+            BlendMode::Multiply => {
+                for i in 0..3 {
+                    out[i] = s[i] * d[i] + s[i] * (1.0 - da) + d[i] * (1.0 - sa);
+                }
+                out[3] = over_alpha;
+            }
+            BlendMode::Screen => {
+                for i in 0..3 {
+                    out[i] = s[i] + d[i] - s[i] * d[i];
+                }
+                out[3] = over_alpha;
+            }
+            BlendMode::Copy => out = s,
+        }
+        self.space.encode(out, to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check a blend's integer fast path against composite_float for every premultiplied
+    /// source and every destination value. Each output channel only depends on that channel
+    /// of the source and destination and on the source's alpha, so all four channels of the
+    /// destination can take the same value.
+    fn check_fast_path(blend: Blend) {
+        for sa in 0..=255u8 {
+            for sc in 0..=sa {
+                let from = [sc, sc, sc, sa];
+                for d in 0..=255u8 {
+                    let mut fast = [d; 4];
+                    let mut float = [d; 4];
+                    blend.composite(&mut fast, &from);
+                    blend.composite_float(&mut float, &from);
+                    assert_eq!(fast, float, "{:?} onto {}", from, d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn over_fast_path_matches_float() {
+        check_fast_path(Blend::new(BlendMode::Over));
+    }
+
+    #[test]
+    fn additive_fast_path_matches_float() {
+        check_fast_path(Blend::new(BlendMode::Additive));
+    }
+}
@@ -1,18 +1,507 @@
-use crate::types::{Rect, Vec2i};
-use fontdue::{Font, Metrics};
-use std::collections::HashMap;
+use crate::screen::Screen;
+use crate::types::{Rect, Rgba};
+use fontdue::{
+    layout::{
+        CoordinateSystem, GlyphRasterConfig, HorizontalAlign, Layout, LayoutSettings, TextStyle,
+        VerticalAlign,
+    },
+    Font, FontSettings, Metrics,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::read;
+use std::io;
+use std::path::{Path, PathBuf};
 
-const NUM_FONTS: usize = 1;
+/// How many rasterized glyphs to keep around for each font by default
+pub const GLYPH_CACHE_SIZE: usize = 256;
 
+/// Names a font in `Fonts`.
+/// The number inside is the `font_index` to use in a `TextStyle`.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct FontId(pub usize);
+
+/// Every font the game uses, each with its own glyph cache
 pub struct Fonts {
-    pub rasterized: HashMap<u64, (Metrics, Vec<u8>)>,
-    pub font_list: [Font; NUM_FONTS],
+    /// Pass this to `Layout::append`; a `FontId` indexes into it
+    pub font_list: Vec<Font>,
+    caches: Vec<GlyphCache>,
+}
+impl Default for Fonts {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Fonts {
-    pub fn new(font_list: [Font; NUM_FONTS]) -> Self {
+    pub fn new() -> Self {
+        Self {
+            font_list: vec![],
+            caches: vec![],
+        }
+    }
+    /// Register a font that's already been parsed
+    pub fn add(&mut self, font: Font) -> FontId {
+        self.font_list.push(font);
+        self.caches.push(GlyphCache::new(GLYPH_CACHE_SIZE));
+        FontId(self.font_list.len() - 1)
+    }
+    /// Load a TTF or OTF file and register it
+    pub fn load(&mut self, path: &Path) -> Result<FontId, FontError> {
+        Ok(self.add(read_font(path)?))
+    }
+    /// Swap in a new version of a font, e.g. after its file changed
+    pub fn replace(&mut self, id: FontId, font: Font) {
+        self.font_list[id.0] = font;
+        self.caches[id.0].clear();
+    }
+    pub fn font(&self, id: FontId) -> &Font {
+        &self.font_list[id.0]
+    }
+    /// The font, if it's been registered
+    pub fn get(&self, id: FontId) -> Option<&Font> {
+        self.font_list.get(id.0)
+    }
+    pub fn len(&self) -> usize {
+        self.font_list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.font_list.is_empty()
+    }
+    /// The glyph cache for one font, e.g. to check its stats
+    pub fn cache(&self, id: FontId) -> &GlyphCache {
+        &self.caches[id.0]
+    }
+    pub fn cache_mut(&mut self, id: FontId) -> &mut GlyphCache {
+        &mut self.caches[id.0]
+    }
+    /// Get a laid out glyph's metrics and coverage bitmap from the cache of the font it uses
+    pub fn glyph(&mut self, key: GlyphRasterConfig) -> &(Metrics, Vec<u8>) {
+        let font = &self.font_list[key.font_index];
+        self.caches[key.font_index].get(font, key)
+    }
+}
+
+impl Fonts {
+    /// Distance from one line's top to the next line's top
+    pub fn line_height(&self, font: FontId, size: f32) -> f32 {
+        self.get(font)
+            .and_then(|f| f.horizontal_line_metrics(size))
+            .map_or(size, |m| m.new_line_size)
+    }
+    /// How far a single line of text advances, or 0 if the font never loaded
+    pub fn text_width(&self, font: FontId, size: f32, text: &str) -> f32 {
+        match self.get(font) {
+            Some(font) => text.chars().map(|c| font.metrics(c, size).advance_width).sum(),
+            None => 0.0,
+        }
+    }
+    /**
+     * Breaks text into lines that fit a width
+     *
+     * Params:
+     *   - text: the text, which may have its own line breaks
+     *   - format: font, size and whether to wrap at all
+     *   - max_width: how wide a line can be, or None for no limit
+     *
+     * Note: Breaks at spaces where it can, and inside words that are too long by themselves
+     **/
+    pub fn wrap_text(&self, text: &str, format: &TextFormat, max_width: Option<f32>) -> Vec<String> {
+        let max_width = match max_width {
+            Some(w) if format.wrap => w,
+            _ => return text.split('\n').map(String::from).collect(),
+        };
+        let width = |t: &str| self.text_width(format.font, format.size, t);
+        let mut lines = vec![];
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", line, word)
+                };
+                if width(&candidate) <= max_width {
+                    line = candidate;
+                    continue;
+                }
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+                // The word goes on a new line, split up if it can't fit on one
+                line = String::new();
+                for c in word.chars() {
+                    line.push(c);
+                    if line.chars().count() > 1 && width(&line) > max_width {
+                        line.pop();
+                        lines.push(line);
+                        line = c.to_string();
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+    /// Width and height of text laid out with `format`, wrapped to `max_width` if given
+    pub fn measure_text(&self, text: &str, format: &TextFormat, max_width: Option<f32>) -> (f32, f32) {
+        let lines = self.wrap_text(text, format, max_width);
+        let w = lines
+            .iter()
+            .map(|l| self.text_width(format.font, format.size, l))
+            .fold(0.0, f32::max);
+        (w, lines.len() as f32 * self.line_height(format.font, format.size))
+    }
+    /// Shorten a line until it fits `max_width` with an ellipsis on the end.
+    /// Lines that already fit are left alone unless `always` is set.
+    fn ellipsize(&self, line: &str, format: &TextFormat, max_width: f32, always: bool) -> String {
+        let mut line = line.to_string();
+        if !always && self.text_width(format.font, format.size, &line) <= max_width {
+            return line;
+        }
+        while !line.is_empty()
+            && self.text_width(format.font, format.size, &format!("{}{}", line, ELLIPSIS)) > max_width
+        {
+            line.pop();
+        }
+        line.push_str(ELLIPSIS);
+        line
+    }
+}
+
+/// Put on the end of text that doesn't fit
+const ELLIPSIS: &str = "...";
+
+/// What to do with text that doesn't fit in its box
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Overflow {
+    /// Draw it anyway
+    Visible,
+    /// Cut it off at the edge of the box
+    Clip,
+    /// Drop the lines that don't fit and end the last one with "..."
+    Ellipsis,
+}
+
+/// How to draw text in a box
+#[derive(PartialEq, Clone, Copy)]
+pub struct TextFormat {
+    pub font: FontId,
+    pub size: f32,
+    pub color: Rgba,
+    pub h_align: HorizontalAlign,
+    pub v_align: VerticalAlign,
+    /// Wrap lines at the box's width
+    pub wrap: bool,
+    pub overflow: Overflow,
+}
+impl TextFormat {
+    pub fn new(size: f32, color: Rgba) -> Self {
+        Self {
+            font: FontId(0),
+            size,
+            color,
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Top,
+            wrap: true,
+            overflow: Overflow::Visible,
+        }
+    }
+    pub fn with_font(self, font: FontId) -> Self {
+        Self { font, ..self }
+    }
+    pub fn with_align(self, h_align: HorizontalAlign, v_align: VerticalAlign) -> Self {
         Self {
-            rasterized: HashMap::new(),
-            font_list,
+            h_align,
+            v_align,
+            ..self
+        }
+    }
+    pub fn with_wrap(self, wrap: bool) -> Self {
+        Self { wrap, ..self }
+    }
+    pub fn with_overflow(self, overflow: Overflow) -> Self {
+        Self { overflow, ..self }
+    }
+}
+
+pub trait DrawTextExt {
+    fn draw_text_box(&mut self, fonts: &mut Fonts, text: &str, rect: Rect, format: &TextFormat);
+}
+
+impl<'fb> DrawTextExt for Screen<'fb> {
+    /**
+     * Draws text wrapped and aligned inside a rect
+     *
+     * Params:
+     *   - fonts: the font registry
+     *   - text: what to write
+     *   - rect: the box to put it in, in world coordinates
+     *   - format: font, size, color, alignment, wrapping and overflow
+     *
+     * Note: Draws nothing if the font isn't in `fonts`, e.g. because it failed to load
+     **/
+    fn draw_text_box(&mut self, fonts: &mut Fonts, text: &str, rect: Rect, format: &TextFormat) {
+        if fonts.get(format.font).is_none() {
+            return;
+        }
+        let line_height = fonts.line_height(format.font, format.size);
+        let mut lines = fonts.wrap_text(text, format, Some(rect.w as f32));
+        if format.overflow == Overflow::Ellipsis {
+            let max_lines = ((rect.h as f32 / line_height) as usize).max(1);
+            let cut = lines.len() > max_lines;
+            lines.truncate(max_lines);
+            for (i, line) in lines.iter_mut().enumerate() {
+                // If lines got dropped the last one gets an ellipsis even if it fits
+                *line = fonts.ellipsize(line, format, rect.w as f32, cut && i == max_lines - 1);
+            }
+        }
+        let height = lines.len() as f32 * line_height;
+        let mut y = rect.y as f32
+            + match format.v_align {
+                VerticalAlign::Top => 0.0,
+                VerticalAlign::Middle => (rect.h as f32 - height) / 2.0,
+                VerticalAlign::Bottom => rect.h as f32 - height,
+            };
+        if format.overflow != Overflow::Visible {
+            self.push_clip(rect);
+        }
+        let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+        for line in lines.iter() {
+            let width = fonts.text_width(format.font, format.size, line);
+            let x = rect.x as f32
+                + match format.h_align {
+                    HorizontalAlign::Left => 0.0,
+                    HorizontalAlign::Center => (rect.w as f32 - width) / 2.0,
+                    HorizontalAlign::Right => rect.w as f32 - width,
+                };
+            layout.reset(&LayoutSettings {
+                x: x.floor(),
+                y: y.floor(),
+                ..LayoutSettings::default()
+            });
+            layout.append(&fonts.font_list, &TextStyle::new(line, format.size, format.font.0));
+            self.draw_text(fonts, &mut layout, format.color);
+            y += line_height;
+        }
+        if format.overflow != Overflow::Visible {
+            self.pop_clip();
+        }
+    }
+}
+
+/// Load a TTF or OTF file without registering it
+pub fn read_font(path: &Path) -> Result<Font, FontError> {
+    let bytes = read(path).map_err(|e| FontError::Io(path.to_path_buf(), e))?;
+    Font::from_bytes(bytes, FontSettings::default()).map_err(|e| FontError::Parse(path.to_path_buf(), e))
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, &'static str),
+}
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(path, e) => write!(f, "couldn't read font {}: {}", path.display(), e),
+            FontError::Parse(path, e) => write!(f, "couldn't parse font {}: {}", path.display(), e),
+        }
+    }
+}
+impl std::error::Error for FontError {}
+
+/// How well a glyph cache is doing
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct CachedGlyph {
+    glyph: (Metrics, Vec<u8>),
+    last_used: u64,
+}
+
+/// Rasterized glyphs for one font, keyed by character and size.
+/// Once it's full the least recently used glyph gets thrown out.
+pub struct GlyphCache {
+    entries: HashMap<GlyphRasterConfig, CachedGlyph>,
+    // last_used -> key, so the oldest entry is always first
+    order: BTreeMap<u64, GlyphRasterConfig>,
+    capacity: usize,
+    tick: u64,
+    stats: CacheStats,
+}
+impl GlyphCache {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Glyph cache needs room for at least one glyph");
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            capacity,
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+    /// Get a glyph's metrics and coverage bitmap, rasterizing it with `font` if it isn't cached
+    pub fn get(&mut self, font: &Font, key: GlyphRasterConfig) -> &(Metrics, Vec<u8>) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.stats.hits += 1;
+            self.order.remove(&entry.last_used);
+            entry.last_used = tick;
+        } else {
+            self.stats.misses += 1;
+            while self.entries.len() >= self.capacity {
+                self.evict_oldest();
+            }
+            self.entries.insert(
+                key,
+                CachedGlyph {
+                    glyph: font.rasterize(key.c, key.px),
+                    last_used: tick,
+                },
+            );
+        }
+        self.order.insert(tick, key);
+        &self.entries[&key].glyph
+    }
+    fn evict_oldest(&mut self) {
+        let oldest = match self.order.keys().next() {
+            Some(&t) => t,
+            None => return,
+        };
+        if let Some(key) = self.order.remove(&oldest) {
+            self.entries.remove(&key);
+            self.stats.evictions += 1;
+        }
+    }
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Change the size cap, evicting old glyphs if there are too many
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0, "Glyph cache needs room for at least one glyph");
+        self.capacity = capacity;
+        while self.entries.len() > self.capacity {
+            self.evict_oldest();
+        }
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Font {
+        read_font(&Path::new(env!("CARGO_MANIFEST_DIR")).join("content/monogram_font.ttf")).unwrap()
+    }
+    fn key(c: char) -> GlyphRasterConfig {
+        GlyphRasterConfig {
+            c,
+            px: 16.0,
+            font_index: 0,
+        }
+    }
+    fn stats(hits: u64, misses: u64, evictions: u64) -> CacheStats {
+        CacheStats {
+            hits,
+            misses,
+            evictions,
+        }
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let font = font();
+        let mut cache = GlyphCache::new(8);
+        cache.get(&font, key('a'));
+        cache.get(&font, key('b'));
+        cache.get(&font, key('a'));
+        cache.get(&font, key('a'));
+        assert_eq!(cache.stats(), stats(2, 2, 0));
+        assert_eq!(cache.len(), 2);
+        // The same character at another size is another glyph
+        cache.get(&font, GlyphRasterConfig { px: 32.0, ..key('a') });
+        assert_eq!(cache.stats(), stats(2, 3, 0));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let font = font();
+        let mut cache = GlyphCache::new(2);
+        cache.get(&font, key('a'));
+        cache.get(&font, key('b'));
+        // Using a makes b the oldest, so c pushes b out
+        cache.get(&font, key('a'));
+        cache.get(&font, key('c'));
+        assert_eq!(cache.stats(), stats(1, 3, 1));
+        assert_eq!(cache.len(), 2);
+        cache.get(&font, key('a'));
+        cache.get(&font, key('c'));
+        assert_eq!(cache.stats(), stats(3, 3, 1));
+        // Now a is the oldest
+        cache.get(&font, key('b'));
+        assert_eq!(cache.stats(), stats(3, 4, 2));
+        cache.get(&font, key('c'));
+        assert_eq!(cache.stats(), stats(4, 4, 2));
+        cache.get(&font, key('a'));
+        assert_eq!(cache.stats(), stats(4, 5, 3));
+    }
+
+    #[test]
+    fn capacity_one_keeps_only_the_last_glyph() {
+        let font = font();
+        let mut cache = GlyphCache::new(1);
+        cache.get(&font, key('a'));
+        cache.get(&font, key('a'));
+        cache.get(&font, key('b'));
+        cache.get(&font, key('a'));
+        assert_eq!(cache.stats(), stats(1, 3, 2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one glyph")]
+    fn capacity_zero_is_refused() {
+        GlyphCache::new(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one glyph")]
+    fn shrinking_to_zero_is_refused() {
+        GlyphCache::new(4).set_capacity(0);
+    }
+
+    #[test]
+    fn shrinking_evicts_the_oldest() {
+        let font = font();
+        let mut cache = GlyphCache::new(4);
+        for c in "abcd".chars() {
+            cache.get(&font, key(c));
         }
+        cache.get(&font, key('a'));
+        cache.set_capacity(2);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats().evictions, 2);
+        // b and c were the oldest, so a and d are still there
+        cache.get(&font, key('a'));
+        cache.get(&font, key('d'));
+        assert_eq!(cache.stats(), stats(3, 4, 2));
     }
 }
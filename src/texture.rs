@@ -1,9 +1,17 @@
-use crate::types::Rect;
-use image::{self, RgbaImage};
-use std::path::Path;
+use crate::types::{Rect, Rgba};
+use image::{self, ImageError, RgbaImage};
+use std::cell::{Ref, RefCell};
+use std::fmt;
+use std::fs::read;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Size of the squares in the missing texture's checkerboard
+const CHECKER_SZ: usize = 8;
 
 pub struct Texture {
-    image: Vec<u8>,
+    // Behind a RefCell so the pixels can be reloaded while sprites share the texture
+    image: RefCell<Vec<u8>>,
     width: usize,
     height: usize,
     depth: usize,
@@ -14,8 +22,30 @@ enum AlphaChannel {
     Last,
 }
 impl Texture {
+    /// Load an image, panicking if it can't be loaded
     pub fn with_file(path: &Path) -> Self {
-        Self::new(image::open(path).expect("Couldn't load image").into_rgba8())
+        Self::load(path).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Load an image, saying what went wrong and with which file if it can't be loaded
+    pub fn load(path: &Path) -> Result<Self, TextureError> {
+        Ok(Self::new(load_image(path)?))
+    }
+    /**
+     * Loads an image, or prints why it couldn't and gives back the missing texture
+     *
+     * Params:
+     *   - path: the image file
+     *   - width, height: how big the fallback should be, so frames cut out of it stay valid
+     **/
+    pub fn load_or_missing(path: &Path, width: usize, height: usize) -> Self {
+        Self::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            Self::missing(width, height)
+        })
+    }
+    /// A magenta and black checkerboard to stand in for images that couldn't be loaded
+    pub fn missing(width: usize, height: usize) -> Self {
+        Self::new(missing_image(width, height))
     }
     pub fn new(image: RgbaImage) -> Self {
         let (width, height) = image.dimensions();
@@ -25,9 +55,26 @@ impl Texture {
             width: width as usize,
             height: height as usize,
             depth: 4,
-            image,
+            image: RefCell::new(image),
         }
     }
+    /**
+     * Reloads the pixels from a file, changing them for everyone sharing this texture
+     *
+     * Params:
+     *   - path: the image file
+     *
+     * Note: The new image has to be the same size, since frames were cut out of the old one.
+     * On any error the texture is left as it was.
+     **/
+    pub fn reload(&self, path: &Path) -> Result<(), TextureError> {
+        let new = Self::load(path)?;
+        if new.size() != self.size() {
+            return Err(TextureError::Resized(path.to_path_buf(), self.size(), new.size()));
+        }
+        *self.image.borrow_mut() = new.image.into_inner();
+        Ok(())
+    }
     pub fn depth(&self) -> usize {
         self.depth
     }
@@ -37,8 +84,112 @@ impl Texture {
     pub fn pitch(&self) -> usize {
         self.width * self.depth
     }
-    pub fn buffer(&self) -> &[u8] {
-        &self.image
+    pub fn buffer(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.image.borrow(), |image| image.as_slice())
+    }
+    /// Copy part of the texture into a new one
+    pub fn crop(&self, rect: Rect) -> Self {
+        assert!(self.valid_frame(rect));
+        let buf = self.buffer();
+        let row = rect.w as usize * self.depth;
+        let mut image = Vec::with_capacity(row * rect.h as usize);
+        for y in (rect.y as usize)..(rect.y as usize + rect.h as usize) {
+            let start = y * self.pitch() + rect.x as usize * self.depth;
+            image.extend_from_slice(&buf[start..(start + row)]);
+        }
+        Self {
+            image: RefCell::new(image),
+            width: rect.w as usize,
+            height: rect.h as usize,
+            depth: self.depth,
+        }
+    }
+    /// Copy all of `src` into this texture with its top left at (x, y), changing it for everyone sharing it
+    pub fn paste(&self, src: &Texture, x: usize, y: usize) {
+        let (w, h) = src.size();
+        assert!(x + w <= self.width && y + h <= self.height, "Pasted texture doesn't fit");
+        assert_eq!(self.depth, src.depth);
+        // A texture only fits on itself at (0, 0), where pasting changes nothing
+        // (and borrowing it twice would panic)
+        if std::ptr::eq(self, src) {
+            return;
+        }
+        let from = src.buffer();
+        let mut to = self.image.borrow_mut();
+        let row = w * self.depth;
+        for (i, src_row) in from.chunks_exact(src.pitch()).enumerate() {
+            let start = (y + i) * self.pitch() + x * self.depth;
+            to[start..(start + row)].copy_from_slice(src_row);
+        }
+    }
+    /// A copy with each color in `from` replaced by the color at the same index in `to`
+    pub fn palette_swap(&self, from: &[Rgba], to: &[Rgba]) -> Self {
+        assert_eq!(from.len(), to.len(), "Palettes need to be the same length");
+        self.map_pixels(|col| {
+            match from
+                .iter()
+                .position(|f| (f.0, f.1, f.2) == (col.0, col.1, col.2) && col.3 > 0)
+            {
+                // Keep the pixel's own coverage on top of the new color's
+                Some(i) => Rgba(to[i].0, to[i].1, to[i].2, mul(to[i].3, col.3)),
+                None => col,
+            }
+        })
+    }
+    /**
+     * Makes a recolored copy
+     *
+     * Params:
+     *   - hue: degrees to turn the hue by
+     *   - saturation: multiplies the saturation
+     *   - value: multiplies the brightness
+     **/
+    pub fn adjust_hsv(&self, hue: f32, saturation: f32, value: f32) -> Self {
+        self.map_pixels(|col| {
+            let (h, s, v) = rgb_to_hsv(col);
+            let h = (h + hue).rem_euclid(360.0);
+            let s = (s * saturation).clamp(0.0, 1.0);
+            let v = (v * value).clamp(0.0, 1.0);
+            let (r, g, b) = hsv_to_rgb(h, s, v);
+            Rgba(r, g, b, col.3)
+        })
+    }
+    /// A copy in shades of grey, keeping how bright each pixel looks
+    pub fn greyscale(&self) -> Self {
+        self.map_pixels(|col| {
+            let luma = 0.299 * col.0 as f32 + 0.587 * col.1 as f32 + 0.114 * col.2 as f32;
+            let l = luma.round() as u8;
+            Rgba(l, l, l, col.3)
+        })
+    }
+    /// A copy with every pixel in one color, keeping the shape's edges
+    pub fn silhouette(&self, col: Rgba) -> Self {
+        self.map_pixels(|px| Rgba(col.0, col.1, col.2, mul(col.3, px.3)))
+    }
+    /// Apply a function to every pixel's straight (not premultiplied) color
+    fn map_pixels(&self, f: impl Fn(Rgba) -> Rgba) -> Self {
+        let mut image = self.buffer().to_vec();
+        for px in image.chunks_exact_mut(self.depth) {
+            let col = f(unpremultiply(Rgba(px[0], px[1], px[2], px[3])));
+            px.copy_from_slice(&[col.0, col.1, col.2, col.3]);
+        }
+        premultiply(&mut image, self.depth, AlphaChannel::Last);
+        Self {
+            image: RefCell::new(image),
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+        }
+    }
+    /// The texture as a plain (not premultiplied) image, e.g. to save it or pack it into an atlas
+    pub fn to_image(&self) -> RgbaImage {
+        let mut image = self.buffer().to_vec();
+        for px in image.chunks_exact_mut(self.depth) {
+            let col = unpremultiply(Rgba(px[0], px[1], px[2], px[3]));
+            px.copy_from_slice(&[col.0, col.1, col.2, col.3]);
+        }
+        RgbaImage::from_raw(self.width as u32, self.height as u32, image)
+            .expect("Texture buffer is the wrong size")
     }
     pub fn valid_frame(&self, frame: Rect) -> bool {
         0 <= frame.x
@@ -48,13 +199,93 @@ impl Texture {
     }
 }
 
+/// Decode an image file without premultiplying it, e.g. to combine it with others first
+pub fn load_image(path: &Path) -> Result<RgbaImage, TextureError> {
+    let bytes = read(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => TextureError::NotFound(path.to_path_buf()),
+        _ => TextureError::Io(path.to_path_buf(), e),
+    })?;
+    let format =
+        image::guess_format(&bytes).map_err(|_| TextureError::Unsupported(path.to_path_buf()))?;
+    let image = image::load_from_memory_with_format(&bytes, format).map_err(|e| match e {
+        ImageError::Unsupported(_) => TextureError::Unsupported(path.to_path_buf()),
+        e => TextureError::Decode(path.to_path_buf(), e),
+    })?;
+    Ok(image.into_rgba8())
+}
+
+/// The missing texture's magenta and black checkerboard, not premultiplied yet
+pub fn missing_image(width: usize, height: usize) -> RgbaImage {
+    RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        if (x as usize / CHECKER_SZ + y as usize / CHECKER_SZ) & 1 == 0 {
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+/// Multiply two 0-255 values as if they were 0-1, rounding to nearest.
+/// Exactly `(a as f32 * b as f32 / 255.0).round()` for every pair, without the floats.
+#[inline(always)]
+pub(crate) fn mul(a: u8, b: u8) -> u8 {
+    let t = a as u32 * b as u32 + 128;
+    ((t + (t >> 8)) >> 8) as u8
+}
+
+/// Undo premultiplication, as well as it can be undone
+fn unpremultiply(col: Rgba) -> Rgba {
+    if col.3 == 0 {
+        return Rgba(0, 0, 0, 0);
+    }
+    let a = col.3 as f32 / 255.0;
+    let c = |v: u8| (v as f32 / a).round().min(255.0) as u8;
+    Rgba(c(col.0), c(col.1), c(col.2), col.3)
+}
+
+/// Hue in degrees, saturation and value from 0 to 1
+fn rgb_to_hsv(col: Rgba) -> (f32, f32, f32) {
+    let (r, g, b) = (col.0 as f32 / 255.0, col.1 as f32 / 255.0, col.2 as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+    (h, s, max)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let to_u8 = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Multiply the color channels by alpha, rounding the same way whichever end alpha is at
 fn premultiply(img: &mut [u8], depth: usize, alpha: AlphaChannel) {
     match alpha {
         AlphaChannel::First => {
             for px in img.chunks_exact_mut(depth) {
-                let a = px[0] as f32 / 255.0;
+                let a = px[0];
                 for component in px[1..].iter_mut() {
-                    *component = (*component as f32 * a).round() as u8;
+                    *component = mul(*component, a);
                 }
                 // swap around to rgba8888
                 let a = px[0];
@@ -66,12 +297,169 @@ fn premultiply(img: &mut [u8], depth: usize, alpha: AlphaChannel) {
         }
         AlphaChannel::Last => {
             for px in img.chunks_exact_mut(depth) {
-                let a = *px.last().unwrap() as f32 / 255.0;
+                let a = *px.last().unwrap();
                 for component in px[0..(depth - 1)].iter_mut() {
-                    *component = (*component as f32 * a) as u8;
+                    *component = mul(*component, a);
                 }
                 // already rgba8888
             }
         }
     }
 }
+
+#[derive(Debug)]
+pub enum TextureError {
+    NotFound(PathBuf),
+    Io(PathBuf, io::Error),
+    /// The file isn't an image format we can read
+    Unsupported(PathBuf),
+    /// The file looked like an image but couldn't be decoded
+    Decode(PathBuf, ImageError),
+    /// A reloaded image isn't the same size as before
+    Resized(PathBuf, (usize, usize), (usize, usize)),
+}
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::NotFound(path) => write!(f, "couldn't find image {}", path.display()),
+            TextureError::Io(path, e) => write!(f, "couldn't read image {}: {}", path.display(), e),
+            TextureError::Unsupported(path) => {
+                write!(f, "{} isn't an image format we can load", path.display())
+            }
+            TextureError::Decode(path, e) => {
+                write!(f, "couldn't decode image {}: {}", path.display(), e)
+            }
+            TextureError::Resized(path, old, new) => write!(
+                f,
+                "image {} changed size from {}x{} to {}x{}",
+                path.display(),
+                old.0,
+                old.1,
+                new.0,
+                new.1
+            ),
+        }
+    }
+}
+impl std::error::Error for TextureError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A texture from straight rgba pixels, one row
+    fn row(pixels: &[Rgba]) -> Texture {
+        let bytes = pixels
+            .iter()
+            .flat_map(|p| vec![p.0, p.1, p.2, p.3])
+            .collect();
+        Texture::new(RgbaImage::from_raw(pixels.len() as u32, 1, bytes).unwrap())
+    }
+    fn pixels(texture: &Texture) -> Vec<Rgba> {
+        texture
+            .to_image()
+            .pixels()
+            .map(|p| Rgba(p[0], p[1], p[2], p[3]))
+            .collect()
+    }
+
+    const RED: Rgba = Rgba(255, 0, 0, 255);
+    const GREEN: Rgba = Rgba(0, 255, 0, 255);
+    const BLUE: Rgba = Rgba(0, 0, 255, 255);
+    const CLEAR: Rgba = Rgba(0, 0, 0, 0);
+
+    #[test]
+    fn palette_swap_keeps_coverage() {
+        let t = row(&[RED, Rgba(255, 0, 0, 128), BLUE, CLEAR]);
+        assert_eq!(
+            pixels(&t.palette_swap(&[RED, BLUE], &[GREEN, Rgba(0, 0, 0, 128)])),
+            vec![GREEN, Rgba(0, 255, 0, 128), Rgba(0, 0, 0, 128), CLEAR]
+        );
+        // Colors not in the palette are left alone
+        assert_eq!(
+            pixels(&t.palette_swap(&[Rgba(1, 2, 3, 255)], &[GREEN])),
+            pixels(&t)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "same length")]
+    fn palette_swap_needs_matching_palettes() {
+        row(&[RED]).palette_swap(&[RED, BLUE], &[GREEN]);
+    }
+
+    #[test]
+    fn adjust_hsv_turns_saturates_and_darkens() {
+        let t = row(&[RED, Rgba(255, 0, 0, 128), Rgba(128, 128, 128, 255)]);
+        assert_eq!(
+            pixels(&t.adjust_hsv(120.0, 1.0, 1.0)),
+            vec![GREEN, Rgba(0, 255, 0, 128), Rgba(128, 128, 128, 255)]
+        );
+        // Hue wraps around either way
+        assert_eq!(pixels(&t.adjust_hsv(-120.0, 1.0, 1.0))[0], BLUE);
+        assert_eq!(pixels(&t.adjust_hsv(480.0, 1.0, 1.0))[0], GREEN);
+        assert_eq!(
+            pixels(&t.adjust_hsv(0.0, 0.0, 1.0))[0],
+            Rgba(255, 255, 255, 255)
+        );
+        assert_eq!(
+            pixels(&t.adjust_hsv(0.0, 1.0, 0.5))[0],
+            Rgba(128, 0, 0, 255)
+        );
+        // Saturation and value stop at 1
+        assert_eq!(pixels(&t.adjust_hsv(0.0, 2.0, 2.0))[0], RED);
+        assert_eq!(pixels(&t.adjust_hsv(0.0, 1.0, 1.0)), pixels(&t));
+    }
+
+    #[test]
+    fn greyscale_keeps_brightness_and_alpha() {
+        let t = row(&[RED, GREEN, BLUE, Rgba(255, 255, 255, 128), CLEAR]);
+        assert_eq!(
+            pixels(&t.greyscale()),
+            vec![
+                Rgba(76, 76, 76, 255),
+                Rgba(150, 150, 150, 255),
+                Rgba(29, 29, 29, 255),
+                Rgba(255, 255, 255, 128),
+                CLEAR,
+            ]
+        );
+    }
+
+    #[test]
+    fn silhouette_fills_the_shape() {
+        let t = row(&[RED, Rgba(0, 255, 0, 128), CLEAR]);
+        let col = Rgba(10, 20, 30, 255);
+        assert_eq!(
+            pixels(&t.silhouette(col)),
+            vec![col, Rgba(10, 20, 30, 128), CLEAR]
+        );
+        // A see-through silhouette color thins out the whole shape
+        let alphas: Vec<u8> = pixels(&t.silhouette(Rgba(10, 20, 30, 128)))
+            .iter()
+            .map(|p| p.3)
+            .collect();
+        assert_eq!(alphas, vec![128, 64, 0]);
+    }
+
+    #[test]
+    fn paste_copies_into_place() {
+        let t = row(&[RED, RED, RED]);
+        t.paste(&row(&[GREEN, BLUE]), 1, 0);
+        assert_eq!(pixels(&t), vec![RED, GREEN, BLUE]);
+    }
+
+    #[test]
+    fn paste_onto_itself_changes_nothing() {
+        let t = row(&[RED, GREEN]);
+        t.paste(&t, 0, 0);
+        assert_eq!(pixels(&t), vec![RED, GREEN]);
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit")]
+    fn paste_has_to_fit() {
+        let t = row(&[RED, GREEN]);
+        t.paste(&t, 1, 0);
+    }
+}
@@ -0,0 +1,762 @@
+use crate::animation::AnimationState;
+use crate::assets::Assets;
+use crate::layers::{LayeredMap, MapLayer};
+use crate::sprite::Sprite;
+use crate::texture::Texture;
+use crate::tiles::{Tile, TileID, Tilemap, Tileset, NO_TILE};
+use crate::types::{Effect, Rect, Vec2i};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Maps made in the Tiled editor (https://www.mapeditor.org), saved as JSON (.tmj/.json) or XML (.tmx).
+// Tile layers need the CSV layer format, and infinite maps aren't supported.
+//
+// Custom properties the loader understands, on tiles in a tileset and on objects:
+//   solid (bool)       tiles only; the player can't walk through it
+//   effect (string)    what touching it does: nothing, fight, win, hurt or speedup
+//   amount (int)       how much hurt or speedup does, 1 if left out
+// and on tile layers:
+//   collides (bool)    sprites bump into its solid tiles
+//   foreground (bool)  it's drawn over the sprites
+//   z (int)            draw order; layers with the same z keep their order in the map
+// Objects and layers keep all their properties, so games can read their own (like an enemy's health).
+// Tile animations made in Tiled's tile animation editor play too.
+
+/// The flip flags Tiled keeps in the top bits of a tile reference
+const GID_FLAGS: u32 = 0xF000_0000;
+/// Tiled times animation frames in milliseconds, but the games count frames at this rate
+const FRAMES_PER_SECOND: usize = 60;
+
+/// A tile layer from a Tiled map
+pub struct TiledLayer {
+    pub name: String,
+    pub tilemap: Tilemap,
+    pub visible: bool,
+    pub opacity: f32,
+    /// Tiled's parallax factor, 1.0 if it has none
+    pub parallax: (f32, f32),
+    pub properties: HashMap<String, String>,
+}
+
+impl TiledLayer {
+    /// A property parsed into some type, if the layer has it and it parses
+    pub fn property<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.properties.get(name).and_then(|v| v.parse().ok())
+    }
+    /// A map layer with this layer's settings and its collides, foreground and z properties
+    pub fn map_layer(&self) -> MapLayer {
+        MapLayer::new(&self.name, self.tilemap.clone())
+            .with_z(self.property("z").unwrap_or(0))
+            .with_visible(self.visible)
+            .with_opacity(self.opacity)
+            .with_parallax(self.parallax.0, self.parallax.1)
+            .with_collides(self.property("collides").unwrap_or(false))
+            .with_foreground(self.property("foreground").unwrap_or(false))
+    }
+}
+
+/// Something placed on an object layer, like an enemy's spawn point
+#[derive(Clone)]
+pub struct TiledObject {
+    pub name: String,
+    /// The object's type (called class in some Tiled versions)
+    pub kind: String,
+    /// Where it is in the world; tile objects are moved so this is their top left
+    pub rect: Rect,
+    /// The tile drawn for a tile object
+    pub tile: Option<(Rc<Tileset>, TileID)>,
+    pub properties: HashMap<String, String>,
+}
+
+impl TiledObject {
+    /// What touching the object does, from its effect and amount properties
+    pub fn effect(&self) -> Result<Effect, String> {
+        parse_effect(&self.properties)
+    }
+    /// A property parsed into some type, if the object has it and it parses
+    pub fn property<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.properties.get(name).and_then(|v| v.parse().ok())
+    }
+    /// A sprite at the object's position showing the given frame
+    pub fn sprite(&self, image: &Rc<Texture>, frame: Rect) -> Sprite {
+        Sprite::new(
+            image,
+            frame,
+            Vec2i(self.rect.x, self.rect.y),
+            true,
+            0,
+            0,
+            AnimationState::Nothing,
+            self.effect().unwrap_or(Effect::Nothing),
+        )
+    }
+    /// A sprite showing the object's own tile, if it's a tile object
+    pub fn tile_sprite(&self) -> Option<Sprite> {
+        self.tile
+            .as_ref()
+            .map(|(tileset, id)| self.sprite(tileset.texture(), tileset.get_rect(*id)))
+    }
+}
+
+/// Everything in a Tiled map
+pub struct TiledMap {
+    /// Size in tiles
+    pub dims: (usize, usize),
+    pub tile_size: (usize, usize),
+    /// Tile layers, bottom first
+    pub layers: Vec<TiledLayer>,
+    /// Objects from every object layer
+    pub objects: Vec<TiledObject>,
+}
+
+impl TiledMap {
+    /**
+     * Loads a Tiled map and the tilesets and images it uses
+     *
+     * Params:
+     *   - assets: where the map's textures and tilesets get loaded into
+     *   - path: the .tmj, .json or .tmx file, relative to the assets' root
+     *
+     * Note: Tilesets are registered in `assets`, external ones under their paths and
+     * embedded ones under their names. Their images fall back to the missing texture
+     * if they can't be loaded.
+     * Each tile layer can only use tiles from one tileset.
+     **/
+    pub fn load<L>(assets: &mut Assets<L>, path: &str) -> Result<Self, TiledError> {
+        let full = assets.root().join(path);
+        let map = read_map(&full)?;
+        let error = |msg: String| TiledError::Format(full.clone(), msg);
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut tilesets = vec![];
+        for entry in map.tilesets {
+            let (first_gid, tileset) = match entry {
+                TilesetEntry::Inline { firstgid, data } => {
+                    let tileset = build_tileset(assets, &data, dir).map_err(error)?;
+                    let handle = assets.add_tileset(&data.name, tileset);
+                    (firstgid, Rc::clone(assets.tileset(handle)))
+                }
+                TilesetEntry::External { firstgid, source } => {
+                    let source = dir.join(source);
+                    (firstgid, load_tileset(assets, &source.to_string_lossy())?)
+                }
+            };
+            tilesets.push((first_gid, tileset));
+        }
+        // Later tilesets start at higher ids, so search from the end
+        tilesets.sort_by_key(|(first_gid, _)| *first_gid);
+        let find = |gid: u32| -> Result<(Rc<Tileset>, TileID), String> {
+            let gid = (gid & !GID_FLAGS) as usize;
+            tilesets
+                .iter()
+                .rev()
+                .find(|(first, _)| *first <= gid)
+                .filter(|(first, tileset)| gid - first < tileset.len())
+                .map(|(first, tileset)| (Rc::clone(tileset), TileID(gid - first)))
+                .ok_or_else(|| format!("no tileset has tile {}", gid))
+        };
+
+        let mut out = Self {
+            dims: (map.width, map.height),
+            tile_size: (map.tilewidth, map.tileheight),
+            layers: vec![],
+            objects: vec![],
+        };
+        let top = Inherited {
+            offset: Vec2i(0, 0),
+            visible: true,
+            opacity: 1.0,
+            parallax: (1.0, 1.0),
+        };
+        let mut layers: Vec<(&LayerData, Inherited)> =
+            map.layers.iter().map(|l| (l, top)).collect();
+        // Group layers get flattened into their children, in order
+        while !layers.is_empty() {
+            let (layer, parent) = layers.remove(0);
+            // Groups' settings combine with their children's, like they do in Tiled
+            let here = Inherited {
+                offset: Vec2i(
+                    parent.offset.0 + layer.offsetx as i32,
+                    parent.offset.1 + layer.offsety as i32,
+                ),
+                visible: parent.visible && layer.visible,
+                opacity: parent.opacity * layer.opacity,
+                parallax: (
+                    parent.parallax.0 * layer.parallaxx,
+                    parent.parallax.1 * layer.parallaxy,
+                ),
+            };
+            let offset = here.offset;
+            match layer.kind.as_str() {
+                "tilelayer" => {
+                    let data = layer.data.as_ref().ok_or_else(|| {
+                        error(format!(
+                            "layer {} has no tiles (is it infinite?)",
+                            layer.name
+                        ))
+                    })?;
+                    let mut tileset: Option<Rc<Tileset>> = None;
+                    let mut ids = Vec::with_capacity(data.len());
+                    for &gid in data.iter() {
+                        if gid == 0 {
+                            ids.push(NO_TILE);
+                            continue;
+                        }
+                        let (set, id) = find(gid).map_err(error)?;
+                        match &tileset {
+                            Some(t) if !Rc::ptr_eq(t, &set) => {
+                                return Err(error(format!(
+                                    "layer {} uses more than one tileset",
+                                    layer.name
+                                )))
+                            }
+                            _ => tileset = Some(set),
+                        }
+                        ids.push(id.0);
+                    }
+                    // An empty layer still needs a tileset to belong to
+                    let tileset =
+                        match tileset.or_else(|| tilesets.first().map(|(_, t)| Rc::clone(t))) {
+                            Some(t) => t,
+                            None => return Err(error("the map has no tilesets".to_string())),
+                        };
+                    if ids.len() != layer.width * layer.height {
+                        return Err(error(format!("layer {} is the wrong size", layer.name)));
+                    }
+                    out.layers.push(TiledLayer {
+                        name: layer.name.clone(),
+                        tilemap: Tilemap::new(offset, (layer.width, layer.height), &tileset, ids),
+                        visible: here.visible,
+                        opacity: here.opacity,
+                        parallax: here.parallax,
+                        properties: properties(&layer.properties),
+                    });
+                }
+                "objectgroup" => {
+                    for object in layer.objects.iter() {
+                        let tile = match object.gid {
+                            Some(gid) => Some(find(gid).map_err(error)?),
+                            None => None,
+                        };
+                        let mut rect = Rect {
+                            x: offset.0 + object.x.round() as i32,
+                            y: offset.1 + object.y.round() as i32,
+                            w: object.width.round() as u16,
+                            h: object.height.round() as u16,
+                        };
+                        if tile.is_some() {
+                            // Tiled puts tile objects by their bottom left corner
+                            rect.y -= rect.h as i32;
+                        }
+                        out.objects.push(TiledObject {
+                            name: object.name.clone(),
+                            kind: object.kind.clone(),
+                            rect,
+                            tile,
+                            properties: properties(&object.properties),
+                        });
+                    }
+                }
+                "group" => {
+                    for (i, child) in layer.layers.iter().enumerate() {
+                        layers.insert(i, (child, here));
+                    }
+                }
+                // Image layers and anything newer aren't used
+                _ => {}
+            }
+        }
+        Ok(out)
+    }
+    /// The tile layer with this name
+    pub fn layer(&self, name: &str) -> Option<&TiledLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+    /// Every object of one type, e.g. all the "enemy"s
+    pub fn objects_of(&self, kind: &str) -> impl Iterator<Item = &TiledObject> {
+        let kind = kind.to_string();
+        self.objects.iter().filter(move |o| o.kind == kind)
+    }
+    /// Just the tilemaps, bottom first
+    pub fn tilemaps(&self) -> Vec<Tilemap> {
+        self.layers.iter().map(|l| l.tilemap.clone()).collect()
+    }
+    /// The tile layers as a layered map
+    pub fn layered(&self) -> LayeredMap {
+        let mut map = LayeredMap::new();
+        for layer in self.layers.iter() {
+            map.add(layer.map_layer());
+        }
+        map
+    }
+}
+
+/// What a layer gets from the groups it's in
+#[derive(Clone, Copy)]
+struct Inherited {
+    offset: Vec2i,
+    visible: bool,
+    opacity: f32,
+    parallax: (f32, f32),
+}
+
+/**
+ * Loads a Tiled tileset file (.tsj, .json or .tsx) and the image it uses,
+ * or gets the copy that's already loaded
+ *
+ * Params:
+ *   - assets: where the tileset and its image get loaded into
+ *   - path: the tileset file, relative to the assets' root; it's registered under this name
+ *
+ * Note: The file is watched, and `Assets::reload_changed` swaps its new tiles into this tileset
+ **/
+pub fn load_tileset<L>(assets: &mut Assets<L>, path: &str) -> Result<Rc<Tileset>, TiledError> {
+    if let Some(handle) = assets.find_tileset(path) {
+        return Ok(Rc::clone(assets.tileset(handle)));
+    }
+    let tileset = read_tileset_file(assets, path)?;
+    let handle = assets.add_tileset(path, tileset);
+    assets.watch_tileset(path, handle);
+    Ok(Rc::clone(assets.tileset(handle)))
+}
+
+/// Read a tileset file under the assets' root into a new `Tileset`, loading its image if need be
+pub(crate) fn read_tileset_file<L>(assets: &mut Assets<L>, path: &str) -> Result<Tileset, TiledError> {
+    let full = assets.root().join(path);
+    let data = read_tileset(&full)?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    build_tileset(assets, &data, dir).map_err(|e| TiledError::Format(full, e))
+}
+
+/// Make a `Tileset` out of a Tiled tileset, loading its image relative to `dir`
+fn build_tileset<L>(
+    assets: &mut Assets<L>,
+    data: &TilesetData,
+    dir: &Path,
+) -> Result<Tileset, String> {
+    let image = dir.join(&data.image);
+    let image = image
+        .to_str()
+        .ok_or_else(|| format!("bad image path {}", image.display()))?;
+    let texture = assets.load_texture_or_missing(image, data.imagewidth, data.imageheight);
+    let mut tiles = vec![
+        Tile {
+            solid: false,
+            collide: Effect::Nothing,
+        };
+        data.tilecount
+    ];
+    for tile in data.tiles.iter() {
+        let props = properties(&tile.properties);
+        let t = tiles
+            .get_mut(tile.id)
+            .ok_or_else(|| format!("tileset {} has no tile {}", data.name, tile.id))?;
+        t.solid = props.get("solid").map(|v| v.as_str()) == Some("true");
+        t.collide = parse_effect(&props)?;
+    }
+    let mut tileset = Tileset::new(tiles, assets.texture(texture))
+        .with_tile_size(data.tilewidth, data.tileheight)
+        .with_spacing(data.margin, data.spacing);
+    for tile in data.tiles.iter().filter(|t| !t.animation.is_empty()) {
+        if let Some(frame) = tile.animation.iter().find(|f| f.tileid >= data.tilecount) {
+            return Err(format!(
+                "tile {} of tileset {} is animated with tile {}, which it doesn't have",
+                tile.id, data.name, frame.tileid
+            ));
+        }
+        // Every frame is shown for at least one game frame
+        let times = tile
+            .animation
+            .iter()
+            .map(|f| ((f.duration * FRAMES_PER_SECOND + 500) / 1000).max(1))
+            .collect();
+        let frames = tile.animation.iter().map(|f| f.tileid).collect();
+        tileset = tileset.with_animation(tile.id, frames, times);
+    }
+    Ok(tileset)
+}
+
+/// Read an effect property like "hurt" and its amount
+fn parse_effect(props: &HashMap<String, String>) -> Result<Effect, String> {
+    let amount = match props.get("amount") {
+        Some(a) => a.parse().map_err(|_| format!("bad amount {}", a))?,
+        None => 1,
+    };
+    match props.get("effect").map(|e| e.as_str()) {
+        None | Some("") | Some("nothing") => Ok(Effect::Nothing),
+        Some("fight") => Ok(Effect::Fight),
+        Some("win") => Ok(Effect::Win),
+        Some("hurt") => Ok(Effect::Hurt(amount)),
+        Some("speedup") => Ok(Effect::Speedup(amount)),
+        Some(other) => Err(format!("unknown effect {}", other)),
+    }
+}
+
+fn properties(props: &[Property]) -> HashMap<String, String> {
+    props
+        .iter()
+        .map(|p| {
+            let value = match &p.value {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            };
+            (p.name.clone(), value)
+        })
+        .collect()
+}
+
+// What's in the files. JSON maps deserialize straight into these, and TMX maps get read into them.
+
+#[derive(Deserialize)]
+struct MapData {
+    width: usize,
+    height: usize,
+    tilewidth: usize,
+    tileheight: usize,
+    #[serde(default)]
+    layers: Vec<LayerData>,
+    #[serde(default)]
+    tilesets: Vec<TilesetEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TilesetEntry {
+    External {
+        firstgid: usize,
+        source: String,
+    },
+    Inline {
+        firstgid: usize,
+        #[serde(flatten)]
+        data: TilesetData,
+    },
+}
+
+#[derive(Deserialize)]
+struct TilesetData {
+    name: String,
+    tilewidth: usize,
+    tileheight: usize,
+    #[serde(default)]
+    margin: usize,
+    #[serde(default)]
+    spacing: usize,
+    tilecount: usize,
+    image: String,
+    imagewidth: usize,
+    imageheight: usize,
+    #[serde(default)]
+    tiles: Vec<TileData>,
+}
+
+#[derive(Deserialize)]
+struct TileData {
+    id: usize,
+    #[serde(default)]
+    properties: Vec<Property>,
+    #[serde(default)]
+    animation: Vec<FrameData>,
+}
+
+#[derive(Deserialize)]
+struct FrameData {
+    tileid: usize,
+    /// In milliseconds
+    duration: usize,
+}
+
+#[derive(Deserialize)]
+struct Property {
+    name: String,
+    value: Value,
+}
+
+fn yes() -> bool {
+    true
+}
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct LayerData {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    width: usize,
+    #[serde(default)]
+    height: usize,
+    /// Tile references, row by row; 0 is an empty cell
+    data: Option<Vec<u32>>,
+    #[serde(default = "yes")]
+    visible: bool,
+    #[serde(default = "one")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default = "one")]
+    parallaxx: f32,
+    #[serde(default = "one")]
+    parallaxy: f32,
+    #[serde(default)]
+    properties: Vec<Property>,
+    #[serde(default)]
+    objects: Vec<ObjectData>,
+    /// A group layer's children
+    #[serde(default)]
+    layers: Vec<LayerData>,
+}
+
+#[derive(Deserialize)]
+struct ObjectData {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type", alias = "class")]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+fn is_xml(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("tmx") | Some("tsx")
+    )
+}
+
+fn read_map(path: &Path) -> Result<MapData, TiledError> {
+    let text = read_to_string(path).map_err(|e| TiledError::Io(path.to_path_buf(), e))?;
+    if is_xml(path) {
+        let doc = roxmltree::Document::parse(&text)
+            .map_err(|e| TiledError::Xml(path.to_path_buf(), e))?;
+        xml_map(doc.root_element()).map_err(|e| TiledError::Format(path.to_path_buf(), e))
+    } else {
+        serde_json::from_str(&text).map_err(|e| TiledError::Json(path.to_path_buf(), e))
+    }
+}
+
+fn read_tileset(path: &Path) -> Result<TilesetData, TiledError> {
+    let text = read_to_string(path).map_err(|e| TiledError::Io(path.to_path_buf(), e))?;
+    if is_xml(path) {
+        let doc = roxmltree::Document::parse(&text)
+            .map_err(|e| TiledError::Xml(path.to_path_buf(), e))?;
+        xml_tileset(doc.root_element()).map_err(|e| TiledError::Format(path.to_path_buf(), e))
+    } else {
+        serde_json::from_str(&text).map_err(|e| TiledError::Json(path.to_path_buf(), e))
+    }
+}
+
+/// An attribute parsed into some type, or `default` if it isn't there
+fn attr<T: std::str::FromStr>(
+    node: roxmltree::Node,
+    name: &str,
+    default: Option<T>,
+) -> Result<T, String> {
+    match node.attribute(name) {
+        Some(v) => v
+            .parse()
+            .map_err(|_| format!("bad {} {:?} on <{}>", name, v, node.tag_name().name())),
+        None => default.ok_or_else(|| format!("<{}> has no {}", node.tag_name().name(), name)),
+    }
+}
+
+fn children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    tag: &'static str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children().filter(move |n| n.has_tag_name(tag))
+}
+
+fn xml_properties(node: roxmltree::Node) -> Vec<Property> {
+    children(node, "properties")
+        .flat_map(|p| children(p, "property"))
+        .map(|p| Property {
+            name: p.attribute("name").unwrap_or("").to_string(),
+            // Multi-line strings go in the element's text instead
+            value: Value::String(
+                p.attribute("value")
+                    .or_else(|| p.text())
+                    .unwrap_or("")
+                    .to_string(),
+            ),
+        })
+        .collect()
+}
+
+fn xml_map(node: roxmltree::Node) -> Result<MapData, String> {
+    if attr(node, "infinite", Some(0))? != 0 {
+        return Err("infinite maps aren't supported".to_string());
+    }
+    let tilesets = children(node, "tileset")
+        .map(|t| {
+            let firstgid = attr(t, "firstgid", None)?;
+            Ok(match t.attribute("source") {
+                Some(source) => TilesetEntry::External {
+                    firstgid,
+                    source: source.to_string(),
+                },
+                None => TilesetEntry::Inline {
+                    firstgid,
+                    data: xml_tileset(t)?,
+                },
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(MapData {
+        width: attr(node, "width", None)?,
+        height: attr(node, "height", None)?,
+        tilewidth: attr(node, "tilewidth", None)?,
+        tileheight: attr(node, "tileheight", None)?,
+        layers: xml_layers(node)?,
+        tilesets,
+    })
+}
+
+fn xml_tileset(node: roxmltree::Node) -> Result<TilesetData, String> {
+    let image = children(node, "image")
+        .next()
+        .ok_or_else(|| "tilesets need to be a single image".to_string())?;
+    let tiles = children(node, "tile")
+        .map(|t| {
+            Ok(TileData {
+                id: attr(t, "id", None)?,
+                properties: xml_properties(t),
+                animation: children(t, "animation")
+                    .flat_map(|a| children(a, "frame"))
+                    .map(|f| {
+                        Ok(FrameData {
+                            tileid: attr(f, "tileid", None)?,
+                            duration: attr(f, "duration", None)?,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(TilesetData {
+        name: attr(node, "name", Some(String::new()))?,
+        tilewidth: attr(node, "tilewidth", None)?,
+        tileheight: attr(node, "tileheight", None)?,
+        margin: attr(node, "margin", Some(0))?,
+        spacing: attr(node, "spacing", Some(0))?,
+        tilecount: attr(node, "tilecount", None)?,
+        image: attr(image, "source", None)?,
+        imagewidth: attr(image, "width", None)?,
+        imageheight: attr(image, "height", None)?,
+        tiles,
+    })
+}
+
+/// The layers directly inside a map or group, in order
+fn xml_layers(node: roxmltree::Node) -> Result<Vec<LayerData>, String> {
+    node.children()
+        .filter(|n| n.is_element())
+        .filter_map(|n| {
+            let kind = match n.tag_name().name() {
+                "layer" => "tilelayer",
+                "objectgroup" => "objectgroup",
+                "group" => "group",
+                _ => return None,
+            };
+            Some(xml_layer(n, kind))
+        })
+        .collect()
+}
+
+fn xml_layer(node: roxmltree::Node, kind: &str) -> Result<LayerData, String> {
+    let name: String = attr(node, "name", Some(String::new()))?;
+    let data = match children(node, "data").next() {
+        Some(data) => {
+            if data.attribute("encoding") != Some("csv") {
+                return Err(format!("layer {} isn't saved as CSV", name));
+            }
+            let ids = data
+                .text()
+                .unwrap_or("")
+                .split(',')
+                .map(|id| {
+                    id.trim()
+                        .parse()
+                        .map_err(|_| format!("bad tile {:?} in layer {}", id, name))
+                })
+                .collect::<Result<_, String>>()?;
+            Some(ids)
+        }
+        None => None,
+    };
+    let objects = children(node, "object")
+        .map(|o| {
+            Ok(ObjectData {
+                name: attr(o, "name", Some(String::new()))?,
+                kind: o
+                    .attribute("type")
+                    .or_else(|| o.attribute("class"))
+                    .unwrap_or("")
+                    .to_string(),
+                x: attr(o, "x", None)?,
+                y: attr(o, "y", None)?,
+                width: attr(o, "width", Some(0.0))?,
+                height: attr(o, "height", Some(0.0))?,
+                gid: o
+                    .attribute("gid")
+                    .map(|_| attr(o, "gid", None))
+                    .transpose()?,
+                properties: xml_properties(o),
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(LayerData {
+        kind: kind.to_string(),
+        width: attr(node, "width", Some(0))?,
+        height: attr(node, "height", Some(0))?,
+        data,
+        visible: attr(node, "visible", Some(1))? != 0,
+        opacity: attr(node, "opacity", Some(1.0))?,
+        offsetx: attr(node, "offsetx", Some(0.0))?,
+        offsety: attr(node, "offsety", Some(0.0))?,
+        parallaxx: attr(node, "parallaxx", Some(1.0))?,
+        parallaxy: attr(node, "parallaxy", Some(1.0))?,
+        properties: xml_properties(node),
+        objects,
+        layers: xml_layers(node)?,
+        name,
+    })
+}
+
+#[derive(Debug)]
+pub enum TiledError {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+    Xml(PathBuf, roxmltree::Error),
+    /// The file parsed but doesn't make a map this loader can use
+    Format(PathBuf, String),
+}
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TiledError::Io(path, e) => write!(f, "couldn't read map {}: {}", path.display(), e),
+            TiledError::Json(path, e) => write!(f, "couldn't parse map {}: {}", path.display(), e),
+            TiledError::Xml(path, e) => write!(f, "couldn't parse map {}: {}", path.display(), e),
+            TiledError::Format(path, e) => write!(f, "can't use map {}: {}", path.display(), e),
+        }
+    }
+}
+impl std::error::Error for TiledError {}
@@ -1,11 +1,17 @@
-use crate::screen::Screen;
+use crate::animation::frame_index;
+use crate::screen::{Blend, Screen};
 use crate::texture::Texture;
 use crate::types::Vec2i;
 use crate::types::{Effect, Rect};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 // Get tiles from sheet and move them with time steps
 
+/// The tile size tilesets get unless they're given another one
 pub const TILE_SZ: usize = 32;
+/// Put this in a map for a cell with no tile in it
+pub const NO_TILE: usize = usize::MAX;
 /// A graphical tile, we'll implement Copy since it's tiny
 #[derive(Clone, Copy)]
 pub struct Tile {
@@ -14,50 +20,176 @@ pub struct Tile {
 }
 /// A set of tiles used in multiple Tilemaps
 pub struct Tileset {
-    // Tile size is a constant, so we can find the tile in the texture using math
+    // Every tile in a set is the same size, so we can find the tile in the texture using math
     // (assuming the texture is a grid of tiles).
-    pub tiles: Vec<Tile>,
+    // The tiles and animations can be swapped out by `replace` while tilemaps share the set.
+    tiles: RefCell<Vec<Tile>>,
     texture: Rc<Texture>,
+    tile_w: usize,
+    tile_h: usize,
+    /// Empty pixels around the edge of the sheet
+    margin: usize,
+    /// Empty pixels between tiles in the sheet
+    spacing: usize,
+    /// Tiles that cycle through other tiles' pictures
+    animations: RefCell<HashMap<usize, TileAnimation>>,
     // In this design, each tileset is a distinct image.
     // Maybe not always the best choice if there aren't many tiles in a tileset!
 }
 /// Indices into a Tileset
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct TileID(usize);
-
-/// Grab a tile with a given ID
-impl std::ops::Index<TileID> for Tileset {
-    type Output = Tile;
-    fn index(&self, id: TileID) -> &Self::Output {
-        &self.tiles[id.0]
+pub struct TileID(pub usize);
+impl TileID {
+    pub fn is_empty(self) -> bool {
+        self.0 == NO_TILE
     }
 }
 
+/// The pictures an animated tile cycles through, timed like an `Animation`.
+/// The tile keeps its own solid and collide whichever picture is showing.
+#[derive(Clone, Debug)]
+pub struct TileAnimation {
+    pub frames: Vec<TileID>,
+    /// How many frames each one is shown for
+    pub times: Vec<usize>,
+}
+
+/// What an empty cell acts like: nothing there to bump into
+const EMPTY: Tile = Tile {
+    solid: false,
+    collide: Effect::Nothing,
+};
+
 impl Tileset {
-    /// Create a new tileset
+    /// Create a new tileset of TILE_SZ square tiles packed edge to edge
     pub fn new(tiles: Vec<Tile>, texture: &Rc<Texture>) -> Self {
         Self {
-            tiles,
+            tiles: RefCell::new(tiles),
             texture: Rc::clone(texture),
+            tile_w: TILE_SZ,
+            tile_h: TILE_SZ,
+            margin: 0,
+            spacing: 0,
+            animations: RefCell::new(HashMap::new()),
+        }
+    }
+    pub fn with_tile_size(self, tile_w: usize, tile_h: usize) -> Self {
+        assert!(tile_w > 0 && tile_h > 0, "Tiles need a size");
+        Self {
+            tile_w,
+            tile_h,
+            ..self
+        }
+    }
+    /// For sheets with a border around the outside and/or gaps between the tiles
+    pub fn with_spacing(self, margin: usize, spacing: usize) -> Self {
+        Self {
+            margin,
+            spacing,
+            ..self
+        }
+    }
+    /**
+     * Makes a tile loop through the pictures of some tiles
+     *
+     * Params:
+     *   - id: the tile to animate
+     *   - frames: the tiles whose pictures it shows, in order
+     *   - times: how many frames each picture is shown for
+     **/
+    pub fn with_animation(mut self, id: usize, frames: Vec<usize>, times: Vec<usize>) -> Self {
+        assert!(id < self.len(), "Animating a tile that doesn't exist");
+        assert!(!frames.is_empty(), "Tile animations need frames");
+        assert_eq!(frames.len(), times.len());
+        assert!(times.iter().sum::<usize>() > 0, "Tile animations need to take some time");
+        assert!(
+            frames.iter().all(|&f| f < self.len()),
+            "Tile animation refers to nonexistent tiles"
+        );
+        self.animations.get_mut().insert(
+            id,
+            TileAnimation {
+                frames: frames.into_iter().map(TileID).collect(),
+                times,
+            },
+        );
+        self
+    }
+    /// Grab a tile with a given ID; empty cells are a tile nothing bumps into
+    pub fn tile(&self, id: TileID) -> Tile {
+        if id.is_empty() {
+            return EMPTY;
+        }
+        self.tiles.borrow()[id.0]
+    }
+    /// How many tiles there are
+    pub fn len(&self) -> usize {
+        self.tiles.borrow().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn animation(&self, id: TileID) -> Option<TileAnimation> {
+        self.animations.borrow().get(&id.0).cloned()
+    }
+    /// The tile whose picture `id` shows at frame `now`; itself unless it's animated
+    pub fn current_tile(&self, id: TileID, now: usize) -> TileID {
+        match self.animations.borrow().get(&id.0) {
+            Some(anim) => anim.frames[frame_index(&anim.times, true, 0, now)],
+            None => id,
         }
     }
+    /// Width and height of each tile in pixels
+    pub fn tile_size(&self) -> (usize, usize) {
+        (self.tile_w, self.tile_h)
+    }
+    pub fn texture(&self) -> &Rc<Texture> {
+        &self.texture
+    }
     /// Get the frame rect for a tile ID
-    fn get_rect(&self, id: TileID) -> Rect {
+    pub fn get_rect(&self, id: TileID) -> Rect {
         let idx = id.0;
         let (w, _h) = self.texture.size();
-        let tw = w / TILE_SZ;
-        let row = idx / tw;
-        let col = idx - (row * tw);
+        // The last column has no spacing after it
+        let columns = ((w.saturating_sub(2 * self.margin) + self.spacing)
+            / (self.tile_w + self.spacing))
+            .max(1);
+        let row = idx / columns;
+        let col = idx - (row * columns);
         Rect {
-            x: col as i32 * TILE_SZ as i32,
-            y: row as i32 * TILE_SZ as i32,
-            w: TILE_SZ as u16,
-            h: TILE_SZ as u16,
+            x: (self.margin + col * (self.tile_w + self.spacing)) as i32,
+            y: (self.margin + row * (self.tile_h + self.spacing)) as i32,
+            w: self.tile_w as u16,
+            h: self.tile_h as u16,
         }
     }
-    /// Does this tileset have a tile for `id`?
+    /**
+     * Swaps in another version of this tileset's tiles and animations, like a reloaded
+     * tileset file, so every tilemap sharing this tileset uses them from then on
+     *
+     * Params:
+     *   - new: the new version
+     *
+     * Note: It has to cut its tiles out of the same texture the same way and can't have
+     * fewer tiles, since tilemaps already refer to them. On any error nothing changes.
+     **/
+    pub fn replace(&self, new: Tileset) -> Result<(), String> {
+        if !Rc::ptr_eq(&self.texture, &new.texture)
+            || (self.tile_w, self.tile_h, self.margin, self.spacing)
+                != (new.tile_w, new.tile_h, new.margin, new.spacing)
+        {
+            return Err("its image or tile layout changed, which needs a restart".to_string());
+        }
+        if new.len() < self.len() {
+            return Err(format!("it went from {} tiles to {}", self.len(), new.len()));
+        }
+        *self.tiles.borrow_mut() = new.tiles.into_inner();
+        *self.animations.borrow_mut() = new.animations.into_inner();
+        Ok(())
+    }
+    /// Does this tileset have a tile for `id`? Empty cells count.
     fn contains(&self, id: TileID) -> bool {
-        id.0 < self.tiles.len()
+        id.is_empty() || id.0 < self.len()
     }
 }
 
@@ -95,24 +227,13 @@ impl Tilemap {
     }
 
     pub fn next_room(&self, direction: usize, dims: (usize, usize), map: Vec<usize>) -> Self {
+        let (w, h) = self.pixel_size();
         let position: Vec2i = match direction {
             //up, right, down, left
-            0 => Vec2i(
-                self.position.0,
-                self.position.1 - (self.dims.1 * TILE_SZ) as i32,
-            ),
-            1 => Vec2i(
-                self.position.0 + (self.dims.0 * TILE_SZ) as i32,
-                self.position.1,
-            ),
-            2 => Vec2i(
-                self.position.0,
-                self.position.1 + (self.dims.1 * TILE_SZ) as i32,
-            ),
-            3 => Vec2i(
-                self.position.0 - (self.dims.0 * TILE_SZ) as i32,
-                self.position.1,
-            ),
+            0 => Vec2i(self.position.0, self.position.1 - h as i32),
+            1 => Vec2i(self.position.0 + w as i32, self.position.1),
+            2 => Vec2i(self.position.0, self.position.1 + h as i32),
+            3 => Vec2i(self.position.0 - w as i32, self.position.1),
             _ => Vec2i(self.position.0, 100),
         };
         print!("{} ", position.1);
@@ -133,8 +254,9 @@ impl Tilemap {
 
     pub fn tile_id_at(&self, Vec2i(x, y): Vec2i) -> (TileID, Vec2i) {
         // Translate into map coordinates
-        let x = (x - self.position.0) / TILE_SZ as i32;
-        let y = (y - self.position.1) / TILE_SZ as i32;
+        let (tw, th) = self.tile_size();
+        let x = (x - self.position.0) / tw as i32;
+        let y = (y - self.position.1) / th as i32;
         assert!(
             x >= 0 && x < self.dims.0 as i32,
             "Tile X coordinate {} out of bounds {}",
@@ -152,35 +274,77 @@ impl Tilemap {
     pub fn size(&self) -> (usize, usize) {
         self.dims
     }
+    /// Width and height of each tile in pixels, from the tileset
+    pub fn tile_size(&self) -> (usize, usize) {
+        self.tileset.tile_size()
+    }
+    /// Width and height of the whole map in pixels
+    pub fn pixel_size(&self) -> (usize, usize) {
+        let (tw, th) = self.tile_size();
+        (self.dims.0 * tw, self.dims.1 * th)
+    }
+    /// Whether a world position is over the map
+    pub fn contains(&self, Vec2i(x, y): Vec2i) -> bool {
+        let (w, h) = self.pixel_size();
+        x >= self.position.0
+            && x < self.position.0 + w as i32
+            && y >= self.position.1
+            && y < self.position.1 + h as i32
+    }
+    /// Where the tile at some map coordinates is in the world
+    pub fn tile_rect(&self, Vec2i(x, y): Vec2i) -> Rect {
+        let (tw, th) = self.tile_size();
+        Rect {
+            x: x * tw as i32 + self.position.0,
+            y: y * th as i32 + self.position.1,
+            w: tw as u16,
+            h: th as u16,
+        }
+    }
     pub fn tile_at(&self, posn: Vec2i) -> (Tile, Vec2i) {
         let tile = self.tile_id_at(posn);
-        (self.tileset[tile.0], tile.1)
+        (self.tileset.tile(tile.0), tile.1)
     }
 
-    pub fn draw(&self, screen: &mut Screen) {
+    /// Draw the visible part of the map, with animated tiles as they are at frame `now`
+    pub fn draw(&self, screen: &mut Screen, now: usize) {
+        self.draw_blend(screen, Vec2i(0, 0), Blend::default(), now);
+    }
+    /**
+     * Draws the visible part of the map
+     *
+     * Params:
+     *   - screen: where to draw it
+     *   - offset: how far from its position to draw it, e.g. for parallax
+     *   - blend: how the tiles are blended onto what's already there
+     *   - now: the frame count, for animated tiles
+     **/
+    pub fn draw_blend(&self, screen: &mut Screen, offset: Vec2i, blend: Blend, now: usize) {
+        let origin = Vec2i(self.position.0 + offset.0, self.position.1 + offset.1);
         let Rect {
             x: sx,
             y: sy,
             w: sw,
             h: sh,
-        } = screen.bounds();
+        } = screen.clip_rect();
         // We'll draw from the topmost/leftmost visible tile to the bottommost/rightmost visible tile.
-        // The camera combined with out position and size tell us what's visible.
+        // The camera and clip combined with out position and size tell us what's visible.
         // leftmost tile: get camera.x into our frame of reference, then divide down to tile units
         // Note that it's also forced inside of 0..self.size.0
 
-        let left = ((sx - self.position.0) / TILE_SZ as i32)
+        let (tw, th) = self.tile_size();
+        let left = ((sx - origin.0) / tw as i32)
             .max(0)
             .min(self.dims.0 as i32) as usize;
         // rightmost tile: same deal, but with screen.x + screen.w.
-        let right = ((sx + ((sw + TILE_SZ as u16) as i32) - self.position.0) / TILE_SZ as i32)
+        let right = ((sx + sw as i32 + tw as i32 - origin.0) / tw as i32)
             .max(0)
             .min(self.dims.0 as i32) as usize;
         // ditto top and bot
-        let top = ((sy - self.position.1) / TILE_SZ as i32)
+        let top = ((sy - origin.1) / th as i32)
             .max(0)
             .min(self.dims.1 as i32) as usize;
-        let bot = ((sy + ((sh + TILE_SZ as u16) as i32) - self.position.1) / TILE_SZ as i32)
+        let bot = ((sy + sh as i32 + th as i32 - origin.1) / th as i32)
             .max(0)
             .min(self.dims.1 as i32) as usize;
 
@@ -190,12 +354,15 @@ impl Tilemap {
             .zip(self.map[(top * self.dims.0)..(bot * self.dims.0)].chunks_exact(self.dims.0))
         {
             // We are in tile coordinates at this point so we'll need to translate back to pixel units and world coordinates to draw.
-            let ypx = (y * TILE_SZ) as i32 + self.position.1;
+            let ypx = (y * th) as i32 + origin.1;
             // Here we can iterate through the column index and the relevant slice of the row in parallel
             for (x, id) in (left..right).zip(row[left..right].iter()) {
-                let xpx = (x * TILE_SZ) as i32 + self.position.0;
-                let frame = self.tileset.get_rect(*id);
-                screen.bitblt(&self.tileset.texture, frame, Vec2i(xpx, ypx), false);
+                if id.is_empty() {
+                    continue;
+                }
+                let xpx = (x * tw) as i32 + origin.0;
+                let frame = self.tileset.get_rect(self.tileset.current_tile(*id, now));
+                screen.bitblt_blend(&self.tileset.texture, frame, Vec2i(xpx, ypx), false, blend);
             }
         }
     }
@@ -1,4 +1,6 @@
-#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -17,6 +19,11 @@ impl Rect {
             r2.y <= r1.y+r1.h as i32
     }
 
+    /// Whether a point is inside the rect
+    pub fn contains(self, Vec2i(x, y): Vec2i) -> bool {
+        self.x <= x && x < self.x + self.w as i32 && self.y <= y && y < self.y + self.h as i32
+    }
+
     pub fn rect_displacement(r1: Rect, r2: Rect) -> Option<(i32, i32)> {
         let x_overlap = (r1.x + r1.w as i32).min(r2.x + r2.w as i32) - r1.x.max(r2.x);
         let y_overlap = (r1.y + r1.h as i32).min(r2.y + r2.h as i32) - r1.y.max(r2.y);
@@ -28,13 +35,13 @@ impl Rect {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
 pub struct Vec2i(pub i32, pub i32);
 
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub struct Rgba(pub u8, pub u8, pub u8, pub u8);
 
-#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
 pub enum Effect {
     Speedup(usize),
     Hurt(usize),
@@ -0,0 +1,30 @@
+// Renders every screen of both games into a headless Canvas and checks it
+// against the goldens in goldens/<game>; see the README to update them.
+use std::path::Path;
+use std::process::Command;
+
+fn check_game(exe: &str, game: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(exe)
+        .current_dir(root)
+        .arg("--golden")
+        .arg(root.join("goldens").join(game))
+        .output()
+        .unwrap_or_else(|e| panic!("couldn't run {}: {}", game, e));
+    assert!(
+        output.status.success(),
+        "{} doesn't match its goldens:\n{}",
+        game,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn game1_matches_goldens() {
+    check_game(env!("CARGO_BIN_EXE_game1"), "game1");
+}
+
+#[test]
+fn game2_matches_goldens() {
+    check_game(env!("CARGO_BIN_EXE_game2"), "game2");
+}
@@ -0,0 +1,126 @@
+// Loads the same small map saved as JSON (map.tmj with tiles.tsj) and as XML
+// (map.tmx with tiles.tsx) from tests/fixtures/tiled and checks what comes out.
+use std::path::Path;
+use Unit2_2D::assets::Assets;
+use Unit2_2D::tiled::TiledMap;
+use Unit2_2D::tiles::{TileID, NO_TILE};
+use Unit2_2D::types::{Effect, Rect, Vec2i};
+
+fn load(file: &str) -> TiledMap {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiled");
+    let mut assets: Assets = Assets::new(&root);
+    TiledMap::load(&mut assets, file).unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn ids(ids: &[usize]) -> Vec<TileID> {
+    ids.iter().copied().map(TileID).collect()
+}
+
+fn check_map(map: &TiledMap) {
+    assert_eq!(map.dims, (4, 3));
+    assert_eq!(map.tile_size, (8, 8));
+
+    // Groups are flattened into their layers, in order
+    let names: Vec<&str> = map.layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["ground", "walls", "hidden", "top"]);
+
+    // The flip flags in the top bits don't change which tile it is
+    let ground = map.layer("ground").unwrap();
+    assert_eq!(
+        ground.tilemap.map,
+        ids(&[0, 1, 1, NO_TILE, 2, 2, 0, NO_TILE, NO_TILE, NO_TILE, 3, 0])
+    );
+    assert_eq!(ground.tilemap.position, Vec2i(0, 0));
+    let (tile, _) = ground.tilemap.tile_at(Vec2i(8, 0));
+    assert_eq!(tile.collide, Effect::Hurt(2));
+    let (tile, _) = ground.tilemap.tile_at(Vec2i(16, 8));
+    assert!(tile.solid);
+
+    // Offsets, opacity and parallax build up through the groups
+    let walls = map.layer("walls").unwrap();
+    assert_eq!(walls.tilemap.position, Vec2i(6, -5));
+    assert_eq!(walls.opacity, 0.25);
+    assert_eq!(walls.parallax, (0.5, 0.5));
+    assert!(walls.visible);
+    assert_eq!(walls.property::<bool>("collides"), Some(true));
+    assert_eq!(walls.map_layer().z(), 2);
+    let hidden = map.layer("hidden").unwrap();
+    assert_eq!(hidden.tilemap.position, Vec2i(5, -8));
+    assert!(!hidden.visible);
+    assert_eq!(hidden.opacity, 0.5);
+
+    // The second tileset's tiles are numbered from its own first gid
+    let top = map.layer("top").unwrap();
+    assert_eq!(
+        top.tilemap.map,
+        ids(&[
+            0, NO_TILE, NO_TILE, NO_TILE, NO_TILE, 2, NO_TILE, NO_TILE, NO_TILE, NO_TILE, NO_TILE,
+            3
+        ])
+    );
+    let (tile, _) = top.tilemap.tile_at(Vec2i(8, 8));
+    assert!(tile.solid);
+    let (tile, _) = top.tilemap.tile_at(Vec2i(0, 0));
+    assert!(!tile.solid);
+
+    // Objects pick up their groups' offsets
+    let door = map.objects_of("exit").next().unwrap();
+    assert_eq!(door.name, "door");
+    assert_eq!(
+        door.rect,
+        Rect {
+            x: 12,
+            y: 8,
+            w: 8,
+            h: 8
+        }
+    );
+    assert_eq!(door.property::<String>("to").as_deref(), Some("floor2"));
+    assert!(door.tile.is_none());
+    // Tile objects are placed by their bottom left corner, and their gids can be flipped too
+    let gem = map.objects_of("item").next().unwrap();
+    assert_eq!(
+        gem.rect,
+        Rect {
+            x: 20,
+            y: 8,
+            w: 8,
+            h: 8
+        }
+    );
+    let (tileset, id) = gem.tile.as_ref().unwrap();
+    assert_eq!(*id, TileID(1));
+    // It's from props, the only tileset whose tile 2 is solid
+    assert!(tileset.tile(TileID(2)).solid);
+}
+
+#[test]
+fn loads_json_maps() {
+    check_map(&load("map.tmj"));
+}
+
+#[test]
+fn loads_xml_maps() {
+    check_map(&load("map.tmx"));
+}
+
+#[test]
+fn external_tilesets_keep_their_animations() {
+    for (file, tileset) in [("map.tmj", "tiles.tsj"), ("map.tmx", "tiles.tsx")].iter() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiled");
+        let mut assets: Assets = Assets::new(&root);
+        let map = TiledMap::load(&mut assets, file).unwrap_or_else(|e| panic!("{}", e));
+        let (tile, _) = map
+            .layer("ground")
+            .unwrap()
+            .tilemap
+            .tile_id_at(Vec2i(16, 16));
+        assert_eq!(tile, TileID(3));
+        // External tilesets are registered under their paths
+        let handle = assets.find_tileset(tileset).unwrap();
+        let anim = assets.tileset(handle).animation(TileID(3)).unwrap();
+        assert_eq!(anim.frames, ids(&[3, 2]));
+        // 100ms is 6 frames at 60 frames a second
+        assert_eq!(anim.times, vec![6, 6]);
+    }
+}